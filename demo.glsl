@@ -9,14 +9,17 @@ layout(push_constant) uniform Constants {
     float aspect;
     vec2 mpos;
     vec2 mclick;
+    float dt;
+    int frame;
+    vec4 date;
 } c;
 
 layout(set = 0, binding = 0) uniform Params {
-    int spokes;
-    float swirl;
-    float cutoff;
-    float speed;
-    vec3 col;
+    int spokes;   // @range(0, 16) @default(5)
+    float swirl;  // @range(0, 4) @default(1)
+    float cutoff; // @default(0.5)
+    float speed;  // @range(-1, 1)
+    vec3 col;     // @color @default(1)
 } u;
 
 float aa_step(float thres, float x) {
@@ -0,0 +1,35 @@
+struct Constants {
+    t: f32,
+    res: vec2<f32>,
+    aspect: f32,
+    mpos: vec2<f32>,
+    mclick: vec2<f32>,
+    dt: f32,
+    frame: i32,
+    date: vec4<f32>,
+}
+var<push_constant> c: Constants;
+
+struct Params {
+    spokes: i32,
+    swirl: f32,
+    cutoff: f32,
+    speed: f32,
+    col: vec3<f32>,
+}
+@group(0) @binding(0) var<uniform> u: Params;
+
+fn aa_step(thres: f32, x: f32) -> f32 {
+    let dx = length(vec2<f32>(dpdx(x), dpdy(x)));
+    return smoothstep(thres - dx, thres + dx, x);
+}
+
+@fragment
+fn main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    let st = vec2<f32>(length(uv), atan2(uv.y, uv.x));
+
+    let v = aa_step(0.0, sin(4.0 * u.swirl / st.x + f32(u.spokes) * st.y + 8.0 * u.speed * c.t))
+        * smoothstep(1.0 - u.cutoff, 1.0 - u.cutoff + 0.7, st.x);
+
+    return vec4<f32>(u.col, 1.0) * vec4<f32>(vec3<f32>(v), 1.0);
+}
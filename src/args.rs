@@ -0,0 +1,135 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+};
+
+use crate::render::export::Export;
+
+pub const USAGE: &str = "\
+Usage: shadervis [options] [fragment.glsl | fragment.wgsl]
+
+Options:
+    --render <out.png>  Render a single frame to a PNG without opening a window
+    --export <out>      Render a range of frames without opening a window, to
+                        a video if <out> is .mp4/.webm/.mkv/.mov (needs ffmpeg)
+                        or numbered images otherwise
+    --size <WxH>        Size of the rendered images (default: 1280x720)
+    --time <seconds>    Time to render a single frame at (default: 0)
+    --from <seconds>    Start of the exported range (default: 0)
+    --to <seconds>      End of the exported range (default: 10)
+    --fps <rate>        Frame rate of the exported range (default: 60)
+    --preset <name>     Start with a preset saved from the Presets window, or
+                        with the values in a JSON file if <name> ends in .json
+    --fallback          Use a software adapter, for machines without a GPU
+    --osc <port>        Listen for OSC messages setting variables on a UDP port
+    --osc-bind <addr>   Address of the interface to listen for OSC on, ex.
+                        0.0.0.0 for every one (default: 127.0.0.1)
+    --osc-send <addr>   Send the values of variables to an OSC client whenever
+                        they change, ex. 192.168.1.20:9001
+    --http <port>       Serve an HTTP and WebSocket API for scripting on a
+                        local port";
+
+// Command line arguments.
+pub struct Args {
+    pub file: PathBuf,
+
+    pub preset: Option<String>,
+
+    // Remote control options.
+    pub osc: Option<u16>,
+    pub osc_bind: IpAddr,
+    pub osc_send: Option<String>,
+    pub http: Option<u16>,
+
+    // Headless rendering options.
+    pub render: Option<PathBuf>,
+    pub export: Option<Export>,
+    pub size: [u32; 2],
+    pub time: f32,
+    pub fallback: bool,
+}
+
+impl Args {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut file = None;
+        let mut render = None;
+        let mut out = None;
+        let mut export = Export::default();
+        let mut size = [1280, 720];
+        let mut time = 0.0;
+        let mut preset = None;
+        let mut fallback = false;
+        let mut osc = None;
+        let mut osc_bind = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let mut osc_send = None;
+        let mut http = None;
+
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            match arg.as_str() {
+                "--render" => render = Some(PathBuf::from(value()?)),
+                "--export" => out = Some(PathBuf::from(value()?)),
+                "--size" => size = parse_size(&value()?)?,
+                "--time" => time = parse_number(&value()?)?,
+                "--from" => export.from = parse_number(&value()?)?,
+                "--to" => export.to = parse_number(&value()?)?,
+                "--fps" => export.fps = parse_number(&value()?)?,
+                "--preset" => preset = Some(value()?),
+                "--fallback" => fallback = true,
+                "--osc" => osc = Some(parse_port(&value()?)?),
+                "--osc-bind" => osc_bind = parse_addr(&value()?)?,
+                "--osc-send" => osc_send = Some(value()?),
+                "--http" => http = Some(parse_port(&value()?)?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ if file.is_none() => file = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument \"{}\"", arg)),
+            }
+        }
+
+        if render.is_some() && out.is_some() {
+            return Err("Only one of --render and --export can be used".to_string());
+        }
+        if export.fps <= 0.0 {
+            return Err(format!("Invalid frame rate {}", export.fps));
+        }
+
+        Ok(Self {
+            file: file.ok_or("Missing fragment shader file")?,
+            preset,
+            osc,
+            osc_bind,
+            osc_send,
+            http,
+            render,
+            export: out.map(|out| Export { out, ..export }),
+            size,
+            time,
+            fallback,
+        })
+    }
+}
+
+// Parse a size like `1920x1080`.
+fn parse_size(s: &str) -> Result<[u32; 2], String> {
+    let err = || format!("Invalid size \"{}\", expected WxH", s);
+    let (w, h) = s.split_once('x').ok_or_else(err)?;
+    let w: u32 = w.parse().map_err(|_| err())?;
+    let h: u32 = h.parse().map_err(|_| err())?;
+    if w == 0 || h == 0 {
+        return Err(err());
+    }
+    Ok([w, h])
+}
+
+fn parse_port(s: &str) -> Result<u16, String> {
+    s.parse().map_err(|_| format!("Invalid port \"{}\"", s))
+}
+
+fn parse_addr(s: &str) -> Result<IpAddr, String> {
+    s.parse().map_err(|_| format!("Invalid address \"{}\"", s))
+}
+
+fn parse_number(s: &str) -> Result<f32, String> {
+    s.parse().map_err(|_| format!("Invalid number \"{}\"", s))
+}
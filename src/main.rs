@@ -4,39 +4,92 @@ use winit::{
     event_loop::{ControlFlow, EventLoop},
 };
 
+mod args;
 mod program;
+mod remote;
 mod render;
 
-
 fn main() {
     env_logger::Builder::from_default_env()
         .parse_filters("shaderview=info")
         .init();
 
     // Parse args
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: shadervis [fragment.glsl]");
-        std::process::exit(1);
-    }
-    let file = std::fs::canonicalize(std::path::PathBuf::from(&args[1])).unwrap();
+    let args = match args::Args::parse(std::env::args()) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{}\n\n{}", e, args::USAGE);
+            std::process::exit(1);
+        }
+    };
+    let file = match std::fs::canonicalize(&args.file) {
+        Ok(file) => file,
+        Err(_) => {
+            println!("File does not exist!");
+            std::process::exit(1);
+        }
+    };
+
+    // Look up the preset to start with, if any
+    let preset = match args.preset.as_ref().map(|name| program::Preset::find(&file, name)) {
+        Some(Ok(preset)) => Some(preset),
+        Some(Err(e)) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
 
-    if !file.exists() {
-        println!("File does not exist!");
-        std::process::exit(1);
+    // Render without a window if requested
+    if args.render.is_some() || args.export.is_some() {
+        if let Err(e) = headless(&file, &args, preset.as_ref()) {
+            log::error!("Error:\n {}", e);
+            std::process::exit(1);
+        }
+        return;
     }
 
     // Create channels for message passing between threads
     let (watch_tx, watch_rx) = std::sync::mpsc::channel();
     let (pipeline_tx, pipeline_rx) = std::sync::mpsc::channel();
+    let (files_tx, files_rx) = std::sync::mpsc::channel();
+    let (remote_tx, remote_rx) = std::sync::mpsc::channel();
+
+    // Listen for remote controllers if requested
+    let osc = match args
+        .osc
+        .map(|port| remote::osc::Osc::spawn(args.osc_bind, port, args.osc_send.as_deref(), remote_tx.clone()))
+    {
+        Some(Ok(osc)) => Some(osc),
+        Some(Err(e)) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
 
     // Initialize the platform event loop
     let event_loop = EventLoop::new();
 
     // Spawn workers
-    let mut renderer = render::Renderer::new(&event_loop, pipeline_rx);
-    program::watcher::spawn(&file, watch_tx);
-    program::compiler::spawn(Arc::clone(&renderer.device), &file, watch_rx, pipeline_tx);
+    let mut renderer = render::Renderer::new(&event_loop, &file, pipeline_rx, watch_tx.clone(), preset, remote_rx, osc);
+    program::watcher::spawn(&file, watch_tx, files_rx);
+    program::compiler::spawn(
+        Arc::clone(&renderer.device),
+        Arc::clone(&renderer.queue),
+        &file,
+        watch_rx,
+        pipeline_tx,
+        files_tx,
+        Arc::clone(&renderer.status),
+    );
+    if let Some(port) = args.http {
+        let program = Arc::clone(&renderer.program);
+        if let Err(e) = remote::http::spawn(port, program, Arc::clone(&renderer.status), remote_tx) {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    }
 
     // Kick off the main event loop
     event_loop.run(move |event, _, control_flow| {
@@ -59,3 +112,34 @@ fn main() {
         renderer.event(&event);
     });
 }
+
+// Render a single frame or a range of frames offscreen, as requested in the args.
+fn headless(
+    file: &std::path::Path,
+    args: &args::Args,
+    preset: Option<&program::Preset>,
+) -> Result<(), String> {
+    let mut headless = render::headless::Headless::new(file, args.size, args.fallback)?;
+    if let Some(preset) = preset {
+        preset.apply(&mut headless.program);
+    }
+    let timeline = program::Timeline::load(file)?;
+
+    if let Some(out) = &args.render {
+        timeline.apply(&mut headless.program, args.time);
+        headless.save(args.time, out)?;
+        log::info!("Wrote {}", out.display());
+    }
+
+    if let Some(export) = &args.export {
+        let export = render::export::Export {
+            timeline: Some(timeline),
+            ..export.clone()
+        };
+        let frames = export.frames();
+        export.run(&mut headless, |i| log::info!("Rendered frame {}/{}", i, frames))?;
+        log::info!("Wrote {} frames to {}", frames, export.out.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::program::Variable;
+
+// An annotation in a trailing comment on a declaration, ex.
+// `uniform texture2D tex; // @image(noise.png)`.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub name: String,
+    pub args: Vec<String>,
+    // The file the annotation was found in, for resolving relative paths.
+    pub file: PathBuf,
+}
+
+impl Annotation {
+    // Resolve a path argument relative to the file the annotation is in.
+    pub fn path(&self, i: usize) -> Option<PathBuf> {
+        let arg = self.args.get(i)?;
+        Some(self.file.parent().unwrap_or_else(|| Path::new("")).join(arg))
+    }
+
+    // Parse every argument as a number, with `true` and `false` as 1 and 0.
+    pub fn numbers(&self) -> Option<Vec<f64>> {
+        self.args
+            .iter()
+            .map(|a| match a.as_str() {
+                "true" => Some(1.0),
+                "false" => Some(0.0),
+                _ => a.parse().ok(),
+            })
+            .collect()
+    }
+}
+
+// How to show a variable in the GUI, from the annotations on its declaration:
+//
+//     float swirl; // How much to swirl @range(0, 10) @step(0.5)
+//     float zoom;  // @range(0.01, 100) @log
+//     vec3 col;    // @color
+//
+// The comment text before any annotations is shown as a tooltip.
+#[derive(Debug, Clone, Default)]
+pub struct Meta {
+    pub range: Option<[f32; 2]>,
+    // Whether sliders are logarithmic, for ranges spanning orders of magnitude.
+    pub log: bool,
+    pub step: Option<f32>,
+    pub color: bool,
+    pub tooltip: Option<String>,
+}
+
+impl Meta {
+    pub fn new(annotations: &Annotations, block: &str, name: &str) -> Self {
+        let range = annotations.numbers(block, name, "range").and_then(|n| match n[..] {
+            [min, max] => Some([min as f32, max as f32]),
+            _ => {
+                log::warn!("Expected @range(min, max) on \"{}\"", name);
+                None
+            }
+        });
+        let step = annotations.numbers(block, name, "step").and_then(|n| match n[..] {
+            [step] => Some(step as f32),
+            _ => {
+                log::warn!("Expected @step(step) on \"{}\"", name);
+                None
+            }
+        });
+
+        Self {
+            range,
+            log: annotations.get(block, name, "log").is_some(),
+            step,
+            color: annotations.get(block, name, "color").is_some(),
+            tooltip: annotations.doc(block, name).map(str::to_string),
+        }
+    }
+}
+
+// Annotations for each declaration, along with the rest of the text in their
+// trailing comments. Declarations are keyed by the name of the block or struct
+// they're a member of, or "" at the top level, and the declared name, so that
+// members with the same name in different blocks are kept apart. Members
+// declared in a file included into a block are found at the top level of that
+// file, so lookups fall back to the top level.
+#[derive(Debug, Default)]
+pub struct Annotations {
+    annotations: HashMap<(String, String), Vec<Annotation>>,
+    docs: HashMap<(String, String), String>,
+}
+
+impl Annotations {
+    // Parse the annotations from the contents of several source files.
+    pub fn parse(sources: &[(PathBuf, String)]) -> Self {
+        let mut annotations = Self::default();
+        for (file, source) in sources {
+            for (block, name, doc, anns) in parse(file, source) {
+                let key = (block, name);
+                if !doc.is_empty() {
+                    annotations.docs.entry(key.clone()).or_insert(doc);
+                }
+                annotations.annotations.entry(key).or_default().extend(anns);
+            }
+        }
+        annotations
+    }
+
+    // Find the first annotation with the given name on a declaration in
+    // `block`, or "" for one at the top level.
+    pub fn get(&self, block: &str, decl: &str, name: &str) -> Option<&Annotation> {
+        lookup(&self.annotations, block, decl)?.iter().find(|a| a.name == name)
+    }
+
+    // The numeric arguments of an annotation on a declaration, warning if
+    // they aren't all numbers.
+    fn numbers(&self, block: &str, decl: &str, name: &str) -> Option<Vec<f64>> {
+        let numbers = self.get(block, decl, name)?.numbers();
+        if numbers.is_none() {
+            log::warn!("Expected numbers in @{} on \"{}\"", name, decl);
+        }
+        numbers
+    }
+
+    // Set a variable to the value in the `@default` annotation on its
+    // declaration if there is one, ex. `@default(2.5)`. Vectors and matrices
+    // take one value for each component, columns first, or one for all of them.
+    pub fn apply_default(&self, block: &str, decl: &str, var: &mut Variable) {
+        let values = match self.numbers(block, decl, "default") {
+            Some(values) => values,
+            None => return,
+        };
+
+        let mut c = var.components();
+        let n = c.iter().map(Vec::len).sum::<usize>();
+        if n == 0 {
+            log::warn!("Arrays and structs can't have a @default, on \"{}\"", decl);
+            return;
+        }
+        if values.len() != 1 && values.len() != n {
+            log::warn!("Expected 1 or {} values in @default on \"{}\"", n, decl);
+            return;
+        }
+        for (i, x) in c.iter_mut().flatten().enumerate() {
+            *x = values[i % values.len()];
+        }
+        var.set_components(&c);
+    }
+
+    // The text of the comment on a declaration before any annotations, ex.
+    // `How much to swirl` for `float swirl; // How much to swirl @range(0, 10)`.
+    pub fn doc(&self, block: &str, decl: &str) -> Option<&str> {
+        lookup(&self.docs, block, decl).map(String::as_str)
+    }
+}
+
+fn lookup<'a, T>(map: &'a HashMap<(String, String), T>, block: &str, decl: &str) -> Option<&'a T> {
+    map.get(&(block.to_string(), decl.to_string()))
+        .or_else(|| map.get(&(String::new(), decl.to_string())))
+}
+
+// Parse the comment text and annotations on each line of a single source file,
+// as (block, name, doc, annotations).
+fn parse(file: &Path, source: &str) -> Vec<(String, String, String, Vec<Annotation>)> {
+    let mut found = vec![];
+
+    // The names of the blocks and structs enclosing the current line, with
+    // None for anything else in braces like function bodies.
+    let mut blocks: Vec<Option<&str>> = vec![];
+    let mut prev = "";
+    for line in source.lines() {
+        let (code, comment) = match line.split_once("//") {
+            Some((code, comment)) => (code, Some(comment)),
+            None => (line, None),
+        };
+
+        // Only the code after the last brace on a line can be a declaration,
+        // ex. `} u;` ending a block.
+        let mut rest = code;
+        while let Some(i) = rest.find(['{', '}']) {
+            if rest[i..].starts_with('{') {
+                // The name may be on the line before, with the brace on its own.
+                let before = if rest[..i].trim().is_empty() { prev } else { &rest[..i] };
+                let name = before.trim_end().rsplit(|c: char| !(c.is_alphanumeric() || c == '_')).next();
+                blocks.push(name.filter(|n| !n.is_empty()));
+            } else {
+                blocks.pop();
+            }
+            rest = &rest[i + 1..];
+        }
+        if !code.trim().is_empty() {
+            prev = code;
+        }
+
+        let comment = match comment {
+            Some(comment) => comment,
+            None => continue,
+        };
+        let decl = match declared_name(rest) {
+            Some(decl) => decl,
+            None => continue,
+        };
+
+        let mut parts = comment.split('@');
+        let doc = parts.next().unwrap_or_default().trim().to_string();
+        let anns = parts.filter_map(|s| annotation(file, s)).collect::<Vec<_>>();
+
+        let block = match blocks.last() {
+            Some(Some(block)) => block,
+            Some(None) => continue,
+            None => "",
+        };
+        if !doc.is_empty() || !anns.is_empty() {
+            found.push((block.to_string(), decl.to_string(), doc, anns));
+        }
+    }
+    found
+}
+
+// Find the name declared by a line of code like `uniform vec3 col;` or `float w[8];`
+// in GLSL, or `var tex: texture_2d<f32>;` and struct members like `swirl: f32,`
+// in WGSL.
+pub fn declared_name(code: &str) -> Option<&str> {
+    let code = code.trim();
+    let code = code.strip_suffix(';').or_else(|| code.strip_suffix(','))?;
+
+    // WGSL puts the type after the name.
+    let code = code.split(':').next()?;
+
+    // Strip any array dimensions.
+    let code = match code.find('[') {
+        Some(i) => &code[..i],
+        None => code,
+    };
+
+    let name = code.trim_end().rsplit(|c: char| !(c.is_alphanumeric() || c == '_')).next()?;
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+// Parse a single annotation like `image(noise.png)` or `color`.
+fn annotation(file: &Path, s: &str) -> Option<Annotation> {
+    let s = s.trim();
+    let end = s.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(s.len());
+    let (name, rest) = s.split_at(end);
+    if name.is_empty() {
+        return None;
+    }
+
+    let args = match rest.trim_start().strip_prefix('(') {
+        Some(rest) => {
+            let args = &rest[..rest.find(')')?];
+            args.split(',')
+                .map(|a| a.trim().trim_matches('"').to_string())
+                .filter(|a| !a.is_empty())
+                .collect()
+        }
+        None => vec![],
+    };
+
+    Some(Annotation {
+        name: name.to_string(),
+        args,
+        file: file.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Annotations {
+        Annotations::parse(&[(PathBuf::from("shaders/main.glsl"), source.to_string())])
+    }
+
+    const GLSL: &str = "
+        layout(set = 0, binding = 0) uniform Params {
+            float swirl; // @range(0, 10) @default(2.5)
+            vec3 col;    // @color
+            float zoom;  // How far to zoom in
+            float speed; // @range(1)
+        } u;
+
+        layout(set = 0, binding = 1) uniform Other
+        {
+            float swirl; // @range(0, 1)
+        } o;
+
+        layout(set = 1, binding = 0) uniform texture2D tex; // @image(noise.png)
+    ";
+
+    #[test]
+    fn range_and_default() {
+        let annotations = parse(GLSL);
+        let meta = Meta::new(&annotations, "Params", "swirl");
+        assert_eq!(meta.range, Some([0.0, 10.0]));
+        assert!(!meta.color);
+        assert_eq!(meta.tooltip, None);
+
+        let mut var = Variable::Float(1.0);
+        annotations.apply_default("Params", "swirl", &mut var);
+        assert!(matches!(var, Variable::Float(x) if x == 2.5));
+    }
+
+    #[test]
+    fn color() {
+        let meta = Meta::new(&parse(GLSL), "Params", "col");
+        assert!(meta.color);
+        assert_eq!(meta.range, None);
+    }
+
+    #[test]
+    fn doc_only() {
+        let meta = Meta::new(&parse(GLSL), "Params", "zoom");
+        assert_eq!(meta.tooltip.as_deref(), Some("How far to zoom in"));
+        assert_eq!(meta.range, None);
+        assert!(!meta.log && !meta.color);
+    }
+
+    #[test]
+    fn malformed_range() {
+        let annotations = parse(GLSL);
+        assert!(annotations.get("Params", "speed", "range").is_some());
+        assert_eq!(Meta::new(&annotations, "Params", "speed").range, None);
+    }
+
+    #[test]
+    fn keyed_by_block() {
+        let annotations = parse(GLSL);
+        assert_eq!(Meta::new(&annotations, "Other", "swirl").range, Some([0.0, 1.0]));
+        assert_eq!(Meta::new(&annotations, "Params", "swirl").range, Some([0.0, 10.0]));
+        assert!(annotations.get("", "swirl", "range").is_none());
+
+        let image = annotations.get("", "tex", "image").unwrap();
+        assert_eq!(image.path(0), Some(PathBuf::from("shaders/noise.png")));
+    }
+
+    #[test]
+    fn wgsl_member() {
+        let annotations = parse(
+            "struct Params {
+                swirl: f32, // @range(0,1)
+            }
+            @group(0) @binding(0) var<uniform> u: Params;
+            @group(1) @binding(0) var tex: texture_2d<f32>; // @image(noise.png)
+            fn main() {
+                let x = 1.0; // @range(2, 3)
+            }",
+        );
+        assert_eq!(Meta::new(&annotations, "Params", "swirl").range, Some([0.0, 1.0]));
+        assert!(annotations.get("", "tex", "image").is_some());
+        assert!(annotations.get("Params", "x", "range").is_none());
+    }
+
+    #[test]
+    fn included_source() {
+        let annotations = Annotations::parse(&[
+            (
+                PathBuf::from("main.glsl"),
+                "layout(set = 0, binding = 0) uniform Params {\n#include \"params.glsl\"\n} u;".to_string(),
+            ),
+            (
+                PathBuf::from("lib/params.glsl"),
+                "float swirl; // @range(0, 4)\nfloat zoom; // @image(zoom.png)".to_string(),
+            ),
+        ]);
+        assert_eq!(Meta::new(&annotations, "Params", "swirl").range, Some([0.0, 4.0]));
+        let image = annotations.get("", "zoom", "image").unwrap();
+        assert_eq!(image.path(0), Some(PathBuf::from("lib/zoom.png")));
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use crate::program::{Program, Texture};
+
+// Names of the offscreen buffer passes, in the order they're rendered.
+pub const NAMES: [&str; 4] = ["A", "B", "C", "D"];
+
+// Use a float format for buffers so they can hold values outside of [0, 1],
+// while still being filterable when sampled.
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// Find the files for each buffer pass next to the main file, ex. `demo.a.glsl`
+// for buffer A of `demo.glsl`. Files are returned whether they exist or not.
+pub fn files(file: &Path) -> Vec<(&'static str, PathBuf)> {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = file.extension().unwrap_or_default().to_string_lossy();
+
+    NAMES
+        .iter()
+        .map(|name| {
+            let filename = format!("{}.{}.{}", stem, name.to_lowercase(), ext);
+            (*name, file.with_file_name(filename))
+        })
+        .collect()
+}
+
+// Find which buffer a texture binding reads from, ex. `BufferA`.
+pub fn input(name: &str) -> Option<usize> {
+    let name = name.strip_prefix("Buffer")?;
+    NAMES.iter().position(|n| *n == name)
+}
+
+// An offscreen pass rendering into a pair of textures, swapping between them
+// every frame so that the pass can read what it rendered the frame before.
+pub struct Buffer {
+    pub name: &'static str,
+    pub program: Program,
+
+    targets: [Texture; 2],
+    // Index of the most recently rendered target.
+    current: usize,
+}
+
+impl Buffer {
+    pub fn new(name: &'static str, program: Program) -> Self {
+        // Start out tiny, the renderer resizes buffers to match the output.
+        let targets = [
+            Texture::target(&program.device, name, [1, 1], FORMAT),
+            Texture::target(&program.device, name, [1, 1], FORMAT),
+        ];
+
+        Self {
+            name,
+            program,
+            targets,
+            current: 0,
+        }
+    }
+
+    // The target holding the last rendered frame.
+    pub fn front(&self) -> &Texture {
+        &self.targets[self.current]
+    }
+
+    // The target to render the next frame into.
+    pub fn back(&self) -> &Texture {
+        &self.targets[1 - self.current]
+    }
+
+    pub fn swap(&mut self) {
+        self.current = 1 - self.current;
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.targets[0].size
+    }
+
+    // Recreate the targets at a new size, clearing their contents.
+    pub fn resize(&mut self, size: [u32; 2]) {
+        for target in &mut self.targets {
+            *target = Texture::target(&self.program.device, self.name, size, FORMAT);
+        }
+    }
+
+    // Carry over the variables and contents of the same buffer from another program.
+    pub fn initialize(&mut self, other: &Buffer) {
+        self.program.initialize(&other.program);
+
+        // Keep the contents so feedback effects don't restart on every reload.
+        self.targets = other.targets.clone();
+        self.current = other.current;
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+// Support for combined `sampler2D` bindings, which wgpu doesn't have. Each
+// declaration like
+//
+//     layout(set = 1, binding = 0) uniform sampler2D tex;
+//
+// is split into a `texture2D` keeping its binding and a `sampler` bound after
+// every other binding in the set, and a macro combines them again wherever the
+// name is used, the same way the ShaderToy prelude does for `iChannel0`:
+//
+//     layout(set = 1, binding = 0) uniform texture2D tex; layout(set = 1, binding = 2) uniform sampler texSampler;
+//     #define tex sampler2D(tex, texSampler)
+//     #line 2
+//
+// Only declarations in the file being compiled are split, not in its includes.
+
+// Rewrite the combined sampler declarations in a GLSL source. Line numbers in
+// errors still match up with the original source.
+pub fn split(source: &str) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    if !lines.iter().any(|line| declaration(line).is_some()) {
+        return source.to_string();
+    }
+
+    // Find the first free binding in each set for the samplers.
+    let mut next = HashMap::new();
+    for line in &lines {
+        if let Some((set, binding)) = line.trim_start().strip_prefix("layout").and_then(layout) {
+            let next = next.entry(set).or_insert(0);
+            *next = (*next).max(binding + 1);
+        }
+    }
+
+    let mut out = String::with_capacity(source.len());
+    for (i, line) in lines.iter().enumerate() {
+        match declaration(line) {
+            Some((set, binding, name)) => {
+                let next = next.entry(set).or_insert(0);
+                let sampler = *next;
+                *next += 1;
+
+                out.push_str(&format!(
+                    "layout(set = {set}, binding = {binding}) uniform texture2D {name}; \
+                     layout(set = {set}, binding = {sampler}) uniform sampler {name}Sampler;\n",
+                    set = set,
+                    binding = binding,
+                    sampler = sampler,
+                    name = name,
+                ));
+                out.push_str(&format!("#define {name} sampler2D({name}, {name}Sampler)\n", name = name));
+                out.push_str(&format!("#line {}\n", i + 2));
+            }
+            None => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+// Parse a declaration like `layout(set = 1, binding = 0) uniform sampler2D tex;`
+// into (set, binding, name).
+fn declaration(line: &str) -> Option<(u32, u32, &str)> {
+    let code = line.split("//").next()?.trim();
+    let rest = code.strip_prefix("layout")?;
+    let (set, binding) = layout(rest)?;
+
+    let rest = &rest[rest.find(')')? + 1..];
+    let rest = rest.trim().strip_suffix(';')?;
+    match rest.split_whitespace().collect::<Vec<_>>()[..] {
+        ["uniform", "sampler2D", name] if name.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+            Some((set, binding, name))
+        }
+        _ => None,
+    }
+}
+
+// Parse the set and binding out of layout qualifiers like `(set = 1, binding = 0)`,
+// with the set defaulting to 0.
+fn layout(qualifiers: &str) -> Option<(u32, u32)> {
+    let qualifiers = qualifiers.trim_start().strip_prefix('(')?;
+    let qualifiers = &qualifiers[..qualifiers.find(')')?];
+
+    let mut set = 0;
+    let mut binding = None;
+    for qualifier in qualifiers.split(',') {
+        match qualifier.split_once('=').map(|(k, v)| (k.trim(), v.trim().parse())) {
+            Some(("set", Ok(value))) => set = value,
+            Some(("binding", Ok(value))) => binding = Some(value),
+            _ => {}
+        }
+    }
+    Some((set, binding?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_declaration() {
+        let source = "#version 450
+layout(set = 1, binding = 0) uniform sampler2D tex; // @image(noise.png)
+layout(set = 1, binding = 1) uniform texture2D other;
+void main() {}
+";
+        let expected = "#version 450
+layout(set = 1, binding = 0) uniform texture2D tex; layout(set = 1, binding = 2) uniform sampler texSampler;
+#define tex sampler2D(tex, texSampler)
+#line 3
+layout(set = 1, binding = 1) uniform texture2D other;
+void main() {}
+";
+        assert_eq!(split(source), expected);
+    }
+
+    #[test]
+    fn bindings_per_set() {
+        let source = "layout(binding = 3) uniform sampler2D a;
+layout(set = 2, binding = 0) uniform sampler2D b;
+layout(binding = 1) uniform sampler2D c;";
+        let split = split(source);
+        assert!(split.contains("layout(set = 0, binding = 4) uniform sampler aSampler;"));
+        assert!(split.contains("layout(set = 2, binding = 1) uniform sampler bSampler;"));
+        assert!(split.contains("layout(set = 0, binding = 5) uniform sampler cSampler;"));
+    }
+
+    #[test]
+    fn unchanged() {
+        let source = "layout(set = 1, binding = 0) uniform texture2D tex;\n// uniform sampler2D old;\n";
+        assert_eq!(split(source), source);
+    }
+}
@@ -1,22 +1,105 @@
 use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashSet},
     fs,
-    path::Path,
+    hash::{Hash, Hasher},
+    path::{Component, Path, PathBuf},
     sync::{
         mpsc::{Receiver, Sender},
-        Arc,
+        Arc, Mutex,
     },
+    time::Instant,
 };
 
-use crate::program::Program;
+use crate::program::{
+    annotation, buffer, combined, diagnostic, error, reflect, shadertoy, Annotations, Buffer, Constants, Diagnostic,
+    Error, Program, State, Status, Struct, Variable,
+};
+
+// The path and contents of every file that went into compiling a shader.
+pub type Sources = Vec<(PathBuf, String)>;
+
+// A compiled fragment shader along with the resources it binds.
+pub struct Shader {
+    pub module: wgpu::ShaderModule,
+    pub entry_point: String,
+    pub bindings: Vec<reflect::Binding>,
+    pub push_constants: Option<Struct>,
+    pub sources: Sources,
+}
 
+// Compile the shader in `file` on a separate thread, and again each time a
+// message comes in on `watch_rx`: `None` to compile the same file, or another
+// file to switch to.
 pub fn spawn(
     device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
     file: &std::path::Path,
-    watch_rx: Receiver<()>,
+    watch_rx: Receiver<Option<PathBuf>>,
     program_tx: Sender<Program>,
+    files_tx: Sender<(PathBuf, HashSet<PathBuf>)>,
+    status: Arc<Mutex<Status>>,
 ) {
-    let file = file.to_owned();
+    let mut file = file.to_owned();
     std::thread::spawn(move || {
+        let mut compiler = Compiler::new(device, queue);
+
+        loop {
+            // Compile the file, create a program from it, and send it to the
+            // renderer. On failure the renderer keeps the last program, and
+            // the errors are shown through the status.
+            {
+                let mut status = status.lock().unwrap();
+                status.file = file.clone();
+                status.state = State::Compiling;
+            }
+            let start = Instant::now();
+            match compiler.program(&file, crate::render::FORMAT) {
+                Ok(program) => {
+                    status.lock().unwrap().succeeded(compiler.hash(), start.elapsed());
+                    if program_tx.send(program).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    log::error!("Error:\n {}", e);
+                    status.lock().unwrap().failed(e.diagnostics(), start.elapsed());
+                }
+            };
+
+            // Let the watcher know which files to watch for changes, which
+            // includes files that failed to compile or be included.
+            if files_tx.send((file.clone(), compiler.files.clone())).is_err() {
+                return;
+            }
+
+            // Wait for a message from the watcher indicating that the file
+            // has changed and we should compile it again, or to switch files.
+            // Stop once the watcher is gone, since nothing will change.
+            match watch_rx.recv() {
+                Ok(Some(new)) => file = new,
+                Ok(None) => {}
+                Err(_) => return,
+            }
+        }
+    });
+}
+
+// Compiles shader files into programs, keeping the state shared between compiles.
+pub struct Compiler {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    compiler: shaderc::Compiler,
+    vertex: wgpu::ShaderModule,
+
+    // Every file read during the last compile, including any included files.
+    pub files: HashSet<PathBuf>,
+    // Hashes the contents of the files that went into the last compile.
+    hasher: DefaultHasher,
+}
+
+impl Compiler {
+    pub fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
         let mut compiler = shaderc::Compiler::new().expect("Failed to create shader compiler!");
 
         // Create the vertex shader module.
@@ -29,94 +112,439 @@ pub fn spawn(
         )
         .expect("Failed to compile vertex shader!");
 
-        loop {
-            // Compile the file, create a program from it, and send it to the renderer.
-            match compile(&device, &mut compiler, shaderc::ShaderKind::Fragment, &file) {
-                Ok((fragment, reflect)) => {
-                    let program = Program::new(Arc::clone(&device), &vertex, fragment, reflect);
-
-                    match program {
-                        Ok(program) => program_tx.send(program).unwrap(),
-                        Err(e) => log::error!("Error:\n {}", e),
-                    };
-                }
-                Err(shaderc::Error::CompilationError(_, err)) => log::error!("Error:\n {}", err),
-                Err(err) => log::error!("{:?}", err),
+        Self {
+            device,
+            queue,
+            compiler,
+            vertex,
+
+            files: HashSet::new(),
+            hasher: DefaultHasher::new(),
+        }
+    }
+
+    // Hash of the contents of every file that went into the last compile.
+    pub fn hash(&self) -> u64 {
+        self.hasher.finish()
+    }
+
+    // Compile the file along with any buffer passes next to it into a
+    // program whose final pass renders to `format`.
+    pub fn program(&mut self, file: &Path, format: wgpu::TextureFormat) -> Result<Program, Error> {
+        self.files.clear();
+        self.hasher = DefaultHasher::new();
+
+        let mut program = self.build(file, format)?;
+        for (name, file) in buffer::files(file) {
+            if file.exists() {
+                let pass = self.build(&file, buffer::FORMAT)?;
+                program.buffers.push(Buffer::new(name, pass));
             }
+        }
+        Ok(program)
+    }
+
+    // Compile a single fragment shader and create a program rendering to `format` from it.
+    // WGSL files are compiled with naga, and anything else is assumed to be GLSL.
+    fn build(&mut self, file: &Path, format: wgpu::TextureFormat) -> Result<Program, Error> {
+        let fragment = match file.extension().and_then(|e| e.to_str()) {
+            Some("wgsl") => {
+                self.files.insert(file.to_owned());
+                compile_wgsl(&self.device, file)?
+            }
+            _ => compile(
+                &self.device,
+                &mut self.compiler,
+                shaderc::ShaderKind::Fragment,
+                file,
+                &mut self.files,
+            )?,
+        };
+        fragment.sources.hash(&mut self.hasher);
 
-            // Wait for a message from the watcher indicating that
-            // the file has changed and we should compile it again.
-            watch_rx.recv().unwrap();
+        // Check that the built-in constants will end up where the shader
+        // expects them, pointing at the declarations that don't match.
+        if let Some(block) = &fragment.push_constants {
+            let errors = reflect::check_constants(block);
+            if !errors.is_empty() {
+                let diagnostics = errors
+                    .into_iter()
+                    .map(|(member, message)| {
+                        let mut diagnostic = Diagnostic::new(&message);
+                        if let Some((file, line)) = locate(&fragment.sources, &block.name, &member) {
+                            diagnostic.file = Some(file);
+                            diagnostic.line = Some(line);
+                        }
+                        diagnostic
+                    })
+                    .collect();
+                return Err(Error::Constants(diagnostics));
+            }
         }
-    });
+
+        let annotations = Annotations::parse(&fragment.sources);
+        Program::new(
+            Arc::clone(&self.device),
+            &self.queue,
+            &self.vertex,
+            fragment,
+            &annotations,
+            format,
+        )
+    }
 }
 
-// Compile a shader from a file into a wgpu shader module along with its reflection data.
+// Compile a GLSL shader from a file into a wgpu shader module along with the
+// resources it binds and the contents of every source file that went into it.
+// The file and every include it tries to resolve are added to `files`, even if
+// compiling fails.
 fn compile(
     device: &wgpu::Device,
     compiler: &mut shaderc::Compiler,
     kind: shaderc::ShaderKind,
     file: &Path,
-) -> Result<(wgpu::ShaderModule, spirv_reflect::ShaderModule), shaderc::Error> {
-    let dir = file.parent().unwrap();
-    let filename = file.file_name().unwrap().to_string_lossy();
+    files: &mut HashSet<PathBuf>,
+) -> Result<Shader, Error> {
+    let dir = file.parent().unwrap_or_else(|| Path::new(""));
+    let filename = file.file_name().unwrap_or_default().to_string_lossy();
+    files.insert(file.to_owned());
+
+    // Keep track of included files, since their contents are needed later
+    // for things like annotations. Includes that fail to resolve are kept
+    // track of too, so that creating or fixing them triggers a recompile,
+    // and the first failure is remembered to report it with its includer.
+    let includes = RefCell::new(vec![]);
+    let resolved = RefCell::new(vec![]);
+    let failed = RefCell::new(None);
 
     // Configure the compiler to try to resolve includes in the same folder
     // as the file being compiled
-    let mut options = shaderc::CompileOptions::new().unwrap();
-    options.set_include_callback(move |file, _ty, _source, _depth| {
+    let mut options = compile_options(device);
+    options.set_include_callback(|file, _ty, source, _depth| {
         let inc = dir.join(Path::new(file));
-        let abs = std::fs::canonicalize(&inc).unwrap();
+        let abs = resolve(&inc);
+        resolved.borrow_mut().push(abs.clone());
 
-        let content = fs::read_to_string(&inc).map_err(|e| e.to_string())?;
+        let content = fs::read_to_string(&abs).map_err(|e| {
+            let error = format!("Failed to read \"{}\": {}", abs.display(), e);
+            failed
+                .borrow_mut()
+                .get_or_insert_with(|| (PathBuf::from(source), file.to_string(), error.clone()));
+            error
+        })?;
+        includes.borrow_mut().push((abs.clone(), content.clone()));
 
         Ok(shaderc::ResolvedInclude {
-            resolved_name: abs.to_str().unwrap().to_string(),
+            resolved_name: abs.to_string_lossy().into_owned(),
             content,
         })
     });
 
-    let content = std::fs::read_to_string(file).expect("Failed to read shader file!");
-    let spirv = compiler.compile_into_spirv(&content, kind, "", "main", Some(&options))?;
+    // Wrap shaders pasted in from ShaderToy with a prelude to make them compile,
+    // and split up combined samplers, which wgpu doesn't support.
+    let content = read(file)?;
+    let source = combined::split(&content);
+    let source = if shadertoy::detect(&content) {
+        log::info!("Compiling \"{}\" in ShaderToy mode.", filename);
+        shadertoy::wrap(&source)
+    } else {
+        source
+    };
+
+    // Name the source after the file, so errors in it point back to the file.
+    let name = file.to_string_lossy();
+    let spirv = compiler.compile_into_spirv(&source, kind, &name, "main", Some(&options));
+    drop(options);
+    files.extend(resolved.into_inner());
+
+    let spirv = spirv.map_err(|e| {
+        let output = match e {
+            shaderc::Error::CompilationError(_, output) => output,
+            e => format!("{:?}", e),
+        };
+
+        // Point failed includes at the line that included them.
+        match failed.into_inner() {
+            Some((file, include, error)) => {
+                let line = diagnostic::parse(&output)
+                    .into_iter()
+                    .find(|d| d.file.as_ref() == Some(&file))
+                    .and_then(|d| d.line);
+                Error::Include {
+                    file,
+                    line,
+                    include,
+                    error,
+                }
+            }
+            None => Error::Compile(output),
+        }
+    })?;
     let source = wgpu::util::make_spirv(spirv.as_binary_u8());
 
-    let reflect_mod = spirv_reflect::create_shader_module(spirv.as_binary_u8())
-        .expect("Failed to reflect shader module!");
-
-    // use naga::{front::glsl, back::spv, valid};
-    // let naga_mod = match glsl::parse_str(
-    //     &content,
-    //     &glsl::Options {
-    //         entry_points: naga::FastHashMap::from_iter(IntoIter::new([(
-    //             "main".to_string(),
-    //             naga::ShaderStage::Fragment,
-    //         )])),
-    //         defines: naga::FastHashMap::from_iter(IntoIter::new([])),
-    //     },
-    // ) {
-    //     Ok(m) => m,
-    //     Err(e) => panic!("{:#?}", e),
-    // };
-
-    // let analysis = valid::Validator::new(
-    //     valid::ValidationFlags::empty(),
-    //     valid::Capabilities::all(),
-    // )
-    // .validate(&naga_mod)
-    // .unwrap();
-
-    // let spirv = spv::write_vec(&naga_mod, &analysis, &spv::Options {
-    //     lang_version: (1, 0),
-    //     flags: spv::WriterFlags::empty(),
-    //     capabilities: None,
-    // }).unwrap();
-
-    let wgpu_mod = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some(filename.as_ref()),
-        source,
-    });
+    let mut sources = vec![(file.to_owned(), content)];
+    sources.extend(includes.into_inner());
+
+    let reflect_mod = spirv_reflect::create_shader_module(spirv.as_binary_u8()).map_err(|e| Error::Reflect {
+        message: e.to_string(),
+        file: None,
+        line: None,
+    })?;
+    let mut bindings = reflect::spirv::bindings(&reflect_mod, "main").map_err(|e| locate_binding(e, &sources))?;
+    for binding in &mut bindings {
+        if let reflect::Resource::Uniform(block) = &mut binding.resource {
+            restore_bools(block, &sources);
+        }
+    }
+    let push_constants = if Constants::push(device) {
+        reflect::spirv::push_constants(&reflect_mod, "main").map_err(|e| push_constants_error(e, &sources))?
+    } else {
+        reflect::take_constants(&mut bindings)
+    };
+
+    // wgpu validates the SPIR-V again, which can catch things glslang doesn't.
+    let module = error::capture(device, || {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(filename.as_ref()),
+            source,
+        })
+    })
+    .map_err(Error::Compile)?;
 
-    Ok((wgpu_mod, reflect_mod))
+    Ok(Shader {
+        module,
+        entry_point: "main".to_string(),
+        bindings,
+        push_constants,
+        sources,
+    })
+}
+
+// Compile a WGSL shader from a file with naga, taking the resources it binds
+// from naga's IR. The entry point is the module's fragment shader.
+fn compile_wgsl(device: &wgpu::Device, file: &Path) -> Result<Shader, Error> {
+    let filename = file.file_name().unwrap_or_default().to_string_lossy();
+    let path = file.to_string_lossy();
+    let content = read(file)?;
+
+    let mut module = naga::front::wgsl::parse_str(&content)
+        .map_err(|e| Error::Compile(e.emit_to_string_with_path(&content, &path)))?;
+
+    // Without push constants, move them into a uniform buffer in the set
+    // reserved for the constants instead.
+    let push = Constants::push(device);
+    if !push {
+        reflect::wgsl::uniform_constants(&mut module);
+    }
+
+    // Validate up front, since naga points its errors at the source, unlike wgpu.
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::PUSH_CONSTANT,
+    )
+    .validate(&module)
+    .map_err(|e| Error::Compile(e.emit_to_string_with_path(&content, &path)))?;
+
+    // Hand wgpu the source as written when it can take it, and otherwise the
+    // module with the constants moved, by way of SPIR-V.
+    let source = if push {
+        wgpu::ShaderSource::Wgsl(content.clone().into())
+    } else {
+        let spirv = naga::back::spv::write_vec(&module, &info, &naga::back::spv::Options::default(), None)
+            .map_err(|e| Error::Compile(format!("Failed to translate \"{}\" to SPIR-V: {}", path, e)))?;
+        wgpu::ShaderSource::SpirV(spirv.into())
+    };
+
+    let entry_point = module
+        .entry_points
+        .iter()
+        .find(|e| e.stage == naga::ShaderStage::Fragment)
+        .map(|e| e.name.clone())
+        .ok_or_else(|| Error::Compile(format!("No fragment shader entry point in \"{}\"", path)))?;
+    let sources = vec![(file.to_owned(), content)];
+    let mut bindings = reflect::wgsl::bindings(&module).map_err(|e| locate_binding(e, &sources))?;
+    let push_constants = if push {
+        reflect::wgsl::push_constants(&module).map_err(|e| push_constants_error(e, &sources))?
+    } else {
+        reflect::take_constants(&mut bindings)
+    };
+
+    let module = error::capture(device, || {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(filename.as_ref()),
+            source,
+        })
+    })
+    .map_err(Error::Compile)?;
+
+    Ok(Shader {
+        module,
+        entry_point,
+        bindings,
+        push_constants,
+        sources,
+    })
+}
+
+// Find where a member of a struct or block is declared in the sources, as
+// (file, line), by looking for it directly inside the body of the struct, so
+// that comments and members of the same name elsewhere aren't mistaken for it.
+fn locate(sources: &Sources, parent: &str, member: &str) -> Option<(PathBuf, u32)> {
+    for (file, source) in sources {
+        // How deep in braces the current line is within the struct's body, or
+        // 0 outside it.
+        let mut depth: u32 = 0;
+        let mut prev = "";
+        for (i, line) in source.lines().enumerate() {
+            let code = line.split("//").next().unwrap_or_default();
+
+            // Only the code after the last brace on a line can be a
+            // declaration, ex. `uniform Params { float swirl;`.
+            let mut rest = code;
+            while let Some(j) = rest.find(['{', '}']) {
+                if rest[j..].starts_with('}') {
+                    depth = depth.saturating_sub(1);
+                } else if depth > 0 {
+                    depth += 1;
+                } else {
+                    // The name may be on the line before, with the brace on its own.
+                    let before = if rest[..j].trim().is_empty() { prev } else { &rest[..j] };
+                    let name = before.trim_end().rsplit(|c: char| !(c.is_alphanumeric() || c == '_')).next();
+                    if name == Some(parent) {
+                        depth = 1;
+                    }
+                }
+                rest = &rest[j + 1..];
+            }
+            if !code.trim().is_empty() {
+                prev = code;
+            }
+
+            if depth == 1 && annotation::declared_name(rest) == Some(member) {
+                return Some((file.clone(), (i + 1) as u32));
+            }
+        }
+    }
+    None
+}
+
+// glslang lowers `bool` members of uniform blocks to `uint`, which are the same
+// size, before reflection ever sees them, so turn members declared as `bool`
+// in the sources back into bools for them to show up as checkboxes. Types
+// spelled through a macro aren't recognized, and stay as `uint`.
+fn restore_bools(block: &mut Struct, sources: &Sources) {
+    for (name, var) in &mut block.vars {
+        if let Variable::Struct(s) = var {
+            restore_bools(s, sources);
+            continue;
+        }
+
+        let code = locate(sources, &block.name, name).and_then(|(file, line)| {
+            let (_, source) = sources.iter().find(|(f, _)| *f == file)?;
+            source.lines().nth(line as usize - 1)
+        });
+        let code = code.unwrap_or_default().split("//").next().unwrap_or_default();
+        let code = code.rsplit(['{', '}']).next().unwrap_or_default();
+        if !code.split(|c: char| !(c.is_alphanumeric() || c == '_')).any(|w| w == "bool") {
+            continue;
+        }
+        match var {
+            Variable::UInt(u) => *var = Variable::Bool(*u != 0),
+            Variable::Array(a) => {
+                for elem in &mut a.elems {
+                    if let Variable::UInt(u) = elem {
+                        *elem = Variable::Bool(*u != 0);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Point an error about an unsupported binding at where it's declared in the sources.
+fn locate_binding(error: Error, sources: &Sources) -> Error {
+    match error {
+        Error::Unsupported {
+            set,
+            binding,
+            name,
+            message,
+            file: None,
+            ..
+        } => {
+            let (file, line) = find(sources, |code| annotation::declared_name(code) == Some(name.as_str())).unzip();
+            Error::Unsupported {
+                set,
+                binding,
+                name,
+                message,
+                file,
+                line,
+            }
+        }
+        error => error,
+    }
+}
+
+// An error from reflecting the push constants, pointing at where they're declared.
+fn push_constants_error(message: String, sources: &Sources) -> Error {
+    let (file, line) = find(sources, |code| code.contains("push_constant")).unzip();
+    Error::Reflect { message, file, line }
+}
+
+// Find the first line of code in the sources that `matches`, as (file, line).
+fn find(sources: &Sources, matches: impl Fn(&str) -> bool) -> Option<(PathBuf, u32)> {
+    sources.iter().find_map(|(file, source)| {
+        let i = source
+            .lines()
+            .position(|l| matches(l.split("//").next().unwrap_or_default()))?;
+        Some((file.clone(), (i + 1) as u32))
+    })
+}
+
+// Turn the path of an include into the absolute path the watcher sees for it.
+// Includes that don't exist can't be canonicalized, so their folder is instead,
+// or failing that the path is normalized lexically, for them to still match up
+// when they're created.
+fn resolve(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    if let (Some(Ok(dir)), Some(name)) = (path.parent().map(fs::canonicalize), path.file_name()) {
+        return dir.join(name);
+    }
+
+    let mut resolved = std::env::current_dir().unwrap_or_default();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+fn read(file: &Path) -> Result<String, Error> {
+    fs::read_to_string(file).map_err(|error| Error::Io {
+        file: file.to_owned(),
+        error,
+    })
+}
+
+// Options for compiling GLSL on a device. Without push constants,
+// `layout(push_constant)` blocks become uniform blocks in the set reserved
+// for the constants, by way of a macro.
+fn compile_options<'a>(device: &wgpu::Device) -> shaderc::CompileOptions<'a> {
+    let mut options = shaderc::CompileOptions::new().unwrap();
+    if !Constants::push(device) {
+        let binding = format!("set = {}, binding = 0", Constants::SET);
+        options.add_macro_definition("push_constant", Some(&binding));
+    }
+    options
 }
 
 // Compile a shader from source in a string into a wgpu shader module.
@@ -127,7 +555,7 @@ fn compile_literal(
     name: &str,
     content: &str,
 ) -> Result<wgpu::ShaderModule, shaderc::Error> {
-    let spirv = compiler.compile_into_spirv(content, kind, name, "main", None)?;
+    let spirv = compiler.compile_into_spirv(content, kind, name, "main", Some(&compile_options(device)))?;
     let source = wgpu::util::make_spirv(spirv.as_binary_u8());
 
     Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -135,3 +563,104 @@ fn compile_literal(
         source,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Array;
+
+    const SOURCE: &str = "#define FLAG bool
+// Params used to have a bool speed;
+layout(set = 0, binding = 0) uniform Other {
+    bool enabled;
+    uint count;
+};
+bool count;
+struct Light {
+    bool on; // uint on;
+};
+layout(set = 0, binding = 1) uniform Params
+{
+    uint enabled; // bool enabled;
+    bool count;
+    bool flags[2];
+    Light light;
+    FLAG hidden;
+    uint speed;
+};";
+
+    fn sources() -> Sources {
+        vec![(PathBuf::from("shader.glsl"), SOURCE.to_string())]
+    }
+
+    // A struct as reflection sees it, with every bool lowered to a uint.
+    fn block(name: &str, vars: Vec<(&str, Variable)>) -> Struct {
+        Struct {
+            name: name.to_string(),
+            offsets: (0..vars.len() as u32).map(|i| i * 16).collect(),
+            size: vars.len() as u32 * 16,
+            vars: vars.into_iter().map(|(name, var)| (name.to_string(), var)).collect(),
+            mat2_stride: 16,
+        }
+    }
+
+    fn is_bool(block: &Struct, name: &str) -> bool {
+        let var = &block.vars.iter().find(|(n, _)| n == name).unwrap().1;
+        match var {
+            Variable::Bool(_) => true,
+            Variable::Array(a) => a.elems.iter().all(|e| matches!(e, Variable::Bool(_))),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn locate_member() {
+        let line = |parent, member| locate(&sources(), parent, member).map(|(_, line)| line);
+        assert_eq!(line("Other", "enabled"), Some(4));
+        assert_eq!(line("Params", "enabled"), Some(13));
+        assert_eq!(line("Params", "count"), Some(14));
+        assert_eq!(line("Light", "on"), Some(9));
+        assert_eq!(line("Params", "on"), None);
+        assert_eq!(line("Missing", "count"), None);
+    }
+
+    #[test]
+    fn bools() {
+        let sources = sources();
+        let mut other = block("Other", vec![("enabled", Variable::UInt(1)), ("count", Variable::UInt(0))]);
+        restore_bools(&mut other, &sources);
+        assert!(is_bool(&other, "enabled"));
+        assert!(matches!(other.vars[0].1, Variable::Bool(true)));
+        assert!(!is_bool(&other, "count"));
+
+        let flags = Variable::Array(Array {
+            stride: 16,
+            elems: vec![Variable::UInt(0), Variable::UInt(0)],
+        });
+        let light = Variable::Struct(block("Light", vec![("on", Variable::UInt(0))]));
+        let mut params = block(
+            "Params",
+            vec![
+                ("enabled", Variable::UInt(0)),
+                ("count", Variable::UInt(0)),
+                ("flags", flags),
+                ("light", light),
+                ("hidden", Variable::UInt(0)),
+                ("speed", Variable::UInt(0)),
+            ],
+        );
+        restore_bools(&mut params, &sources);
+
+        // Declared as a bool in another block, and in a comment.
+        assert!(!is_bool(&params, "enabled"));
+        assert!(is_bool(&params, "count"));
+        assert!(is_bool(&params, "flags"));
+        match &params.vars[3].1 {
+            Variable::Struct(light) => assert!(is_bool(light, "on")),
+            _ => panic!("Expected a struct"),
+        }
+        // Macros aren't expanded.
+        assert!(!is_bool(&params, "hidden"));
+        assert!(!is_bool(&params, "speed"));
+    }
+}
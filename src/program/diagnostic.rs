@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+// A single error or warning from compiling a shader, along with where in the
+// source it came from when the compiler says.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(message: &str) -> Self {
+        Self {
+            file: None,
+            line: None,
+            column: None,
+            message: message.trim().to_string(),
+        }
+    }
+
+    // A `file:line` string for jumping to the diagnostic in an editor.
+    pub fn location(&self) -> Option<String> {
+        let file = self.file.as_ref()?.display();
+        Some(match self.line {
+            Some(line) => format!("{}:{}", file, line),
+            None => file.to_string(),
+        })
+    }
+}
+
+// Split up an error message into diagnostics. Understands the output of
+// glslang (through shaderc), which looks like:
+//
+//     /path/to/file.glsl:12: error: 'foo' : undeclared identifier
+//
+// and naga, which puts the location on the line after the message:
+//
+//     error: no definition in scope for identifier: 'foo'
+//       ┌─ /path/to/file.wgsl:12:5
+//
+// Anything else, like errors from creating the program, becomes a single
+// diagnostic without a location.
+pub fn parse(error: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    for line in error.lines() {
+        if let Some(location) = line.trim_start().strip_prefix("┌─ ") {
+            if let Some(last) = diagnostics.last_mut().filter(|d| d.file.is_none()) {
+                let mut parts = location.rsplitn(3, ':');
+                let column = parts.next().and_then(|c| c.parse().ok());
+                let line = parts.next().and_then(|l| l.parse().ok());
+                if let (Some(file), Some(line)) = (parts.next(), line) {
+                    last.file = Some(PathBuf::from(file));
+                    last.line = Some(line);
+                    last.column = column;
+                }
+            }
+        } else if let Some(diagnostic) = glslang(line) {
+            diagnostics.push(diagnostic);
+        } else if line.starts_with("error:") || line.starts_with("warning:") {
+            diagnostics.push(Diagnostic::new(line));
+        }
+    }
+
+    if diagnostics.is_empty() && !error.trim().is_empty() {
+        diagnostics.push(Diagnostic::new(error));
+    }
+    diagnostics
+}
+
+// Parse a single line of glslang output, ex. `file.glsl:12: error: message`.
+// The file name comes first and may itself contain colons.
+fn glslang(line: &str) -> Option<Diagnostic> {
+    let i = line.find(": error: ").or_else(|| line.find(": warning: "))?;
+    let (file, number) = line[..i].rsplit_once(':')?;
+    let number = number.parse().ok()?;
+
+    let mut diagnostic = Diagnostic::new(&line[i + 1..]);
+    diagnostic.file = Some(PathBuf::from(file)).filter(|f| !f.as_os_str().is_empty());
+    diagnostic.line = Some(number);
+    Some(diagnostic)
+}
@@ -0,0 +1,110 @@
+use std::{fmt, io, path::PathBuf};
+
+use crate::program::{diagnostic, Diagnostic};
+
+// Everything that can go wrong turning a shader file into a program.
+#[derive(Debug)]
+pub enum Error {
+    // A shader file couldn't be read.
+    Io { file: PathBuf, error: io::Error },
+    // An `#include` in `file` couldn't be resolved, on `line` if the compiler
+    // said where.
+    Include {
+        file: PathBuf,
+        line: Option<u32>,
+        include: String,
+        error: String,
+    },
+    // The shader failed to compile, with the compiler's output.
+    Compile(String),
+    // The compiled shader couldn't be reflected, pointing at the declaration
+    // responsible in `file` on `line` when it could be found.
+    Reflect {
+        message: String,
+        file: Option<PathBuf>,
+        line: Option<u32>,
+    },
+    // The shader binds a resource named `name` of a kind that isn't supported,
+    // declared in `file` on `line` if it could be found.
+    Unsupported {
+        set: u32,
+        binding: u32,
+        name: String,
+        message: String,
+        file: Option<PathBuf>,
+        line: Option<u32>,
+    },
+    // The shader's push constants don't match the built-in constants.
+    Constants(Vec<Diagnostic>),
+    // The shader's bindings don't fit into a pipeline, or creating it failed.
+    Pipeline(String),
+}
+
+impl Error {
+    // Split the error up into diagnostics to show, with locations where known.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Error::Compile(output) => diagnostic::parse(output),
+            Error::Constants(diagnostics) => diagnostics.clone(),
+            Error::Io { file, .. } => vec![Diagnostic {
+                file: Some(file.clone()),
+                line: None,
+                column: None,
+                message: self.to_string(),
+            }],
+            Error::Include { file, line, .. } => vec![Diagnostic {
+                file: Some(file.clone()),
+                line: *line,
+                column: None,
+                message: self.to_string(),
+            }],
+            Error::Reflect { file, line, .. } | Error::Unsupported { file, line, .. } => vec![Diagnostic {
+                file: file.clone(),
+                line: *line,
+                column: None,
+                message: self.to_string(),
+            }],
+            _ => diagnostic::parse(&self.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { file, error } => write!(f, "Failed to read \"{}\": {}", file.display(), error),
+            Error::Include { include, error, .. } => write!(f, "Failed to include \"{}\": {}", include, error),
+            Error::Compile(output) => write!(f, "{}", output),
+            Error::Reflect { message, .. } => write!(f, "Failed to reflect shader: {}", message),
+            Error::Unsupported { set, binding, message, .. } => {
+                write!(f, "{} (set {}, binding {})", message, set, binding)
+            }
+            Error::Constants(diagnostics) => {
+                for (i, d) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    match d.location() {
+                        Some(location) => write!(f, "{}: {}", location, d.message)?,
+                        None => write!(f, "{}", d.message)?,
+                    }
+                }
+                Ok(())
+            }
+            Error::Pipeline(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Run `f` with any wgpu validation errors it causes captured and returned,
+// rather than going to the uncaptured error handler, which panics.
+pub fn capture<T>(device: &wgpu::Device, f: impl FnOnce() -> T) -> Result<T, String> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(e) => Err(e.to_string()),
+        None => Ok(value),
+    }
+}
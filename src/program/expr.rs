@@ -0,0 +1,333 @@
+// A small arithmetic expression language for computing variable values, ex.
+// `0.5 + 0.3 * sin(t * 2)`. Supports numbers, named values, `+ - * / % ^`,
+// parentheses and a handful of functions.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Number(f64),
+    Name(String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(format!("Unexpected \"{}\" at {}", c, parser.pos + 1)),
+        }
+    }
+
+    // Evaluate the expression, looking up names other than the constants `pi`
+    // and `tau` with `lookup`.
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Result<f64, String> {
+        Ok(match self {
+            Expr::Number(x) => *x,
+            Expr::Name(name) => match name.as_str() {
+                "pi" => std::f64::consts::PI,
+                "tau" => std::f64::consts::TAU,
+                _ => lookup(name).ok_or_else(|| format!("Unknown name \"{}\"", name))?,
+            },
+            Expr::Neg(e) => -e.eval(lookup)?,
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(lookup)?, b.eval(lookup)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    '%' => a.rem_euclid(b),
+                    _ => a.powf(b),
+                }
+            }
+            Expr::Call(name, args) => {
+                let args = args.iter().map(|a| a.eval(lookup)).collect::<Result<Vec<_>, _>>()?;
+                call(name, &args)?
+            }
+        })
+    }
+}
+
+// Call a built-in function, checking the number of arguments.
+fn call(name: &str, args: &[f64]) -> Result<f64, String> {
+    let f1 = |f: fn(f64) -> f64| -> Result<f64, String> {
+        match args {
+            [x] => Ok(f(*x)),
+            _ => Err(format!("{}() takes 1 argument", name)),
+        }
+    };
+    let f2 = |f: fn(f64, f64) -> f64| -> Result<f64, String> {
+        match args {
+            [x, y] => Ok(f(*x, *y)),
+            _ => Err(format!("{}() takes 2 arguments", name)),
+        }
+    };
+    let f3 = |f: fn(f64, f64, f64) -> f64| -> Result<f64, String> {
+        match args {
+            [x, y, z] => Ok(f(*x, *y, *z)),
+            _ => Err(format!("{}() takes 3 arguments", name)),
+        }
+    };
+
+    match name {
+        "sin" => f1(f64::sin),
+        "cos" => f1(f64::cos),
+        "tan" => f1(f64::tan),
+        "asin" => f1(f64::asin),
+        "acos" => f1(f64::acos),
+        "atan" => f1(f64::atan),
+        "abs" => f1(f64::abs),
+        "sign" => f1(f64::signum),
+        "floor" => f1(f64::floor),
+        "ceil" => f1(f64::ceil),
+        "round" => f1(f64::round),
+        "fract" => f1(|x| x - x.floor()),
+        "sqrt" => f1(f64::sqrt),
+        "exp" => f1(f64::exp),
+        "log" => f1(f64::ln),
+        "saw" => f1(saw),
+        "square" => f1(square),
+        "noise" => f1(noise),
+        "min" => f2(f64::min),
+        "max" => f2(f64::max),
+        "pow" => f2(f64::powf),
+        "mod" => f2(f64::rem_euclid),
+        "atan2" => f2(f64::atan2),
+        "step" => f2(|edge, x| (x >= edge) as u32 as f64),
+        "clamp" => f3(|x, lo, hi| x.max(lo).min(hi)),
+        "mix" => f3(|a, b, t| a + (b - a) * t),
+        "smoothstep" => f3(|lo, hi, x| {
+            let t = ((x - lo) / (hi - lo)).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        }),
+        _ => Err(format!("Unknown function \"{}\"", name)),
+    }
+}
+
+// A sawtooth wave rising from -1 to 1 over each unit of `x`.
+pub fn saw(x: f64) -> f64 {
+    2.0 * (x - x.floor()) - 1.0
+}
+
+// A square wave that's 1 for the first half of each unit of `x` and -1 for the second.
+pub fn square(x: f64) -> f64 {
+    if x - x.floor() < 0.5 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+// Smooth value noise between -1 and 1, with a new random value at every integer.
+pub fn noise(x: f64) -> f64 {
+    fn hash(i: f64) -> f64 {
+        let mut h = (i as i64 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        h ^= h >> 32;
+        h = h.wrapping_mul(0xd6e8_feb8_6659_fd93);
+        h ^= h >> 32;
+        (h as u32) as f64 / u32::MAX as f64 * 2.0 - 1.0
+    }
+
+    let i = x.floor();
+    let t = x - i;
+    let t = t * t * (3.0 - 2.0 * t);
+    hash(i) + (hash(i + 1.0) - hash(i)) * t
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    // Consume the next character if it's one of `ops`.
+    fn op(&mut self, ops: &str) -> Option<char> {
+        self.skip_whitespace();
+        let c = self.peek().filter(|c| ops.contains(*c))?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.op(&c.to_string())
+            .map(|_| ())
+            .ok_or_else(|| format!("Expected \"{}\" at {}", c, self.pos + 1))
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        while let Some(op) = self.op("+-") {
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.op("*/%") {
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.op("-").is_some() {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    // power := atom ('^' unary)?, so `2^3^2` is `2^(3^2)` and `2^-1` works.
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if self.op("^").is_some() {
+            return Ok(Expr::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    // atom := number | name | name '(' (expr (',' expr)*)? ')' | '(' expr ')'
+    fn atom(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                number
+                    .parse()
+                    .map(Expr::Number)
+                    .map_err(|_| format!("Invalid number \"{}\" at {}", number, start + 1))
+            }
+            // Names can have dots in them to pick out components, ex. `mpos.x`.
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+
+                if self.op("(").is_none() {
+                    return Ok(Expr::Name(name));
+                }
+                let mut args = vec![];
+                if self.op(")").is_none() {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.op(",").is_none() {
+                            break;
+                        }
+                    }
+                    self.expect(')')?;
+                }
+                Ok(Expr::Call(name, args))
+            }
+            Some(c) => Err(format!("Unexpected \"{}\" at {}", c, start + 1)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str) -> Result<f64, String> {
+        Expr::parse(source)?.eval(&|name| match name {
+            "t" => Some(2.0),
+            "tint.x" => Some(0.25),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1+2*3"), Ok(7.0));
+        assert_eq!(eval("(1+2)*3"), Ok(9.0));
+        assert_eq!(eval("10-4-3"), Ok(3.0));
+        assert_eq!(eval("12/2/3"), Ok(2.0));
+        assert_eq!(eval("2*3^2"), Ok(18.0));
+        assert_eq!(eval("-7 % 3"), Ok(2.0));
+        assert_eq!(eval(" 1 +\t2 "), Ok(3.0));
+    }
+
+    #[test]
+    fn power() {
+        assert_eq!(eval("2^3^2"), Ok(512.0));
+        assert_eq!(eval("(2^3)^2"), Ok(64.0));
+        assert_eq!(eval("-2^2"), Ok(-4.0));
+        assert_eq!(eval("(-2)^2"), Ok(4.0));
+        assert_eq!(eval("2^-1"), Ok(0.5));
+        assert_eq!(eval("--2"), Ok(2.0));
+    }
+
+    #[test]
+    fn names_and_calls() {
+        assert_eq!(eval("t * tint.x"), Ok(0.5));
+        assert_eq!(eval("pi"), Ok(std::f64::consts::PI));
+        assert_eq!(eval("max(1, t)"), Ok(2.0));
+        assert_eq!(eval("clamp(5, 0, t)"), Ok(2.0));
+        assert_eq!(eval("mix(0, 10, tint.x)"), Ok(2.5));
+        assert_eq!(eval("step(0.5, fract(1.75))"), Ok(1.0));
+        assert_eq!(eval("abs(sin(0))"), Ok(0.0));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(eval("sin()"), Err("sin() takes 1 argument".to_string()));
+        assert_eq!(eval("sin(1, 2)"), Err("sin() takes 1 argument".to_string()));
+        assert_eq!(eval("max(1)"), Err("max() takes 2 arguments".to_string()));
+        assert_eq!(eval("clamp(1, 2)"), Err("clamp() takes 3 arguments".to_string()));
+        assert_eq!(eval("frob(1)"), Err("Unknown function \"frob\"".to_string()));
+        assert_eq!(eval("speed * 2"), Err("Unknown name \"speed\"".to_string()));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(eval("1 2"), Err("Unexpected \"2\" at 3".to_string()));
+        assert_eq!(eval("1 + 2)"), Err("Unexpected \")\" at 6".to_string()));
+        assert_eq!(eval("(1 + 2"), Err("Expected \")\" at 7".to_string()));
+        assert_eq!(eval("1 +"), Err("Unexpected end of expression".to_string()));
+        assert_eq!(eval("1..2"), Err("Invalid number \"1..2\" at 1".to_string()));
+        assert_eq!(eval("max(1,)"), Err("Unexpected \")\" at 7".to_string()));
+        assert_eq!(eval("* 2"), Err("Unexpected \"*\" at 1".to_string()));
+    }
+
+    #[test]
+    fn waves() {
+        assert_eq!([saw(0.0), saw(0.25), saw(0.5), saw(1.25)], [-1.0, -0.5, 0.0, -0.5]);
+        assert_eq!([square(0.0), square(0.25), square(0.5), square(-0.25)], [1.0, 1.0, -1.0, -1.0]);
+        for i in 0..40 {
+            let x = i as f64 * 0.25 - 5.0;
+            assert!((-1.0..=1.0).contains(&noise(x)));
+        }
+        // Noise is continuous across integers, where its random value changes.
+        assert!((noise(3.0 - 1e-9) - noise(3.0)).abs() < 1e-6);
+    }
+}
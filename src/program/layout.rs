@@ -0,0 +1,167 @@
+use crate::program::Variable;
+
+// Helpers implementing the std140 layout rules from the GLSL spec (section 7.6.2.2
+// of the OpenGL 4.5 core profile). The offsets reflected from the SPIR-V are what
+// actually ends up in the uniform buffer, but these are used to double check them
+// and to reason about where each variable should land.
+
+// Round `x` up to the next multiple of `align`.
+pub fn round_up(x: u32, align: u32) -> u32 {
+    if align == 0 {
+        return x;
+    }
+    x.div_ceil(align) * align
+}
+
+// Compute the std140 offset of each variable in a block, along with the total
+// size of the block including any padding at the end.
+pub fn offsets<'a>(vars: impl IntoIterator<Item = &'a Variable>) -> (Vec<u32>, u32) {
+    let mut offsets = vec![];
+    let mut offset = 0;
+    let mut align = 16;
+    for var in vars {
+        offset = round_up(offset, var.align());
+        offsets.push(offset);
+        offset += var.size();
+        align = align.max(var.align());
+    }
+
+    // Structures are padded out to a multiple of their base alignment, which is
+    // itself rounded up to the alignment of a vec4.
+    (offsets, round_up(offset, align))
+}
+
+// Compute the std140 stride of an array with elements like `elem`, which is
+// rounded up to the alignment of a vec4.
+pub fn array_stride(elem: &Variable) -> u32 {
+    round_up(round_up(elem.size(), elem.align()), 16)
+}
+
+// Check the variables of a block against the offsets reflected from the shader,
+// recursing into arrays and nested structs and returning a description of the
+// first mismatch found.
+pub fn validate(vars: &[(String, Variable)], reflected: &[u32]) -> Result<(), String> {
+    let (computed, _) = offsets(vars.iter().map(|(_, v)| v));
+    for ((name, var), (computed, reflected)) in vars.iter().zip(computed.iter().zip(reflected)) {
+        if computed != reflected {
+            return Err(format!(
+                "Variable \"{}\" is at offset {} but std140 places it at {}",
+                name, reflected, computed
+            ));
+        }
+        validate_var(name, var)?;
+    }
+    Ok(())
+}
+
+fn validate_var(name: &str, var: &Variable) -> Result<(), String> {
+    match var {
+        Variable::Array(a) => {
+            if let Some(elem) = a.elems.first() {
+                let stride = array_stride(elem);
+                if a.stride != stride {
+                    return Err(format!(
+                        "Array \"{}\" has a stride of {} but std140 requires {}",
+                        name, a.stride, stride
+                    ));
+                }
+                validate_var(&format!("{}[0]", name), elem)?;
+            }
+            Ok(())
+        }
+        Variable::Struct(s) => validate(&s.vars, &s.offsets)
+            .map_err(|e| format!("In \"{}\": {}", name, e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::{Array, Struct};
+
+    fn vars(vars: &[Variable]) -> Vec<(String, Variable)> {
+        vars.iter().enumerate().map(|(i, v)| (format!("v{}", i), v.clone())).collect()
+    }
+
+    #[test]
+    fn float_packs_after_vec3() {
+        let (offsets, size) = offsets(&[Variable::Vec3([0.0; 3]), Variable::Float(0.0)]);
+        assert_eq!(offsets, vec![0, 12]);
+        assert_eq!(size, 16);
+    }
+
+    #[test]
+    fn vec3_aligns_to_16() {
+        let (offsets, size) = offsets(&[Variable::Float(0.0), Variable::Vec3([0.0; 3])]);
+        assert_eq!(offsets, vec![0, 16]);
+        assert_eq!(size, 32);
+    }
+
+    #[test]
+    fn nested_struct_is_padded() {
+        let inner = [Variable::Float(0.0)];
+        let (inner_offsets, inner_size) = offsets(&inner);
+        assert_eq!(inner_size, 16);
+
+        let inner = Variable::Struct(Struct {
+            name: "Inner".to_string(),
+            vars: vars(&inner),
+            offsets: inner_offsets,
+            size: inner_size,
+            mat2_stride: 16,
+        });
+        let (offsets, size) = offsets(&[inner, Variable::Float(0.0)]);
+        assert_eq!(offsets, vec![0, 16]);
+        assert_eq!(size, 32);
+    }
+
+    #[test]
+    fn float_array_stride() {
+        assert_eq!(array_stride(&Variable::Float(0.0)), 16);
+
+        let array = Variable::Array(Array {
+            stride: 16,
+            elems: vec![Variable::Float(0.0); 8],
+        });
+        assert_eq!(array.size(), 128);
+        let (offsets, _) = offsets(&[array.clone(), Variable::Float(0.0)]);
+        assert_eq!(offsets, vec![0, 128]);
+        assert!(validate(&vars(&[array]), &[0]).is_ok());
+
+        let packed = Variable::Array(Array {
+            stride: 4,
+            elems: vec![Variable::Float(0.0); 8],
+        });
+        assert!(validate(&vars(&[packed]), &[0]).is_err());
+    }
+
+    #[test]
+    fn mat3_column_stride() {
+        let mat = Variable::Mat3([[1.0; 3], [2.0; 3], [3.0; 3]]);
+        assert_eq!(mat.size(), 48);
+        let bytes = mat.bytes(16);
+        assert_eq!(bytes.len(), 48);
+        assert_eq!(bytes[16..20], 2.0f32.to_le_bytes());
+        assert_eq!(bytes[32..36], 3.0f32.to_le_bytes());
+
+        let (offsets, _) = offsets(&[mat, Variable::Float(0.0)]);
+        assert_eq!(offsets, vec![0, 48]);
+    }
+
+    #[test]
+    fn validate_rejects_wrong_offsets() {
+        let block = vars(&[Variable::Float(0.0), Variable::Vec3([0.0; 3])]);
+        assert!(validate(&block, &[0, 16]).is_ok());
+        assert!(validate(&block, &[0, 4]).is_err());
+
+        let inner = Variable::Struct(Struct {
+            name: "Inner".to_string(),
+            vars: block,
+            offsets: vec![0, 12],
+            size: 32,
+            mat2_stride: 16,
+        });
+        assert!(validate(&vars(&[inner]), &[0]).is_err());
+    }
+}
@@ -1,170 +1,218 @@
-use spirv_reflect::types::{ReflectDescriptorType, ReflectTypeFlags};
 use std::{collections::HashMap, sync::Arc};
 
+mod annotation;
+pub mod buffer;
+mod combined;
 pub mod compiler;
+pub mod diagnostic;
+mod error;
+mod expr;
+mod layout;
+pub mod modulator;
+pub mod morph;
+pub mod preset;
+pub mod reflect;
+mod shadertoy;
+mod status;
+mod texture;
+pub mod timeline;
 mod uniform;
 pub mod watcher;
 
-pub use uniform::{Constants, Uniform, UniformGroup, Variable};
+pub use annotation::{Annotations, Meta};
+pub use buffer::Buffer;
+pub use compiler::Shader;
+pub use diagnostic::Diagnostic;
+pub use error::Error;
+pub use expr::Expr;
+pub use modulator::{Modulator, Modulators};
+pub use morph::{Morph, Snapshot};
+pub use preset::{Preset, Presets};
+pub use status::{State, Status};
+pub use texture::{Sampler, Texture};
+pub use timeline::Timeline;
+pub use uniform::{Array, Constants, ConstantsBuffer, Struct, Uniform, UniformGroup, Variable};
+
+use reflect::{Binding, Resource};
 
 pub struct Program {
+    pub device: Arc<wgpu::Device>,
     pub consts: Constants,
 
     pub pipeline: wgpu::RenderPipeline,
     pub uniform_groups: HashMap<u32, UniformGroup>,
+    // Where the constants go on devices without push constants.
+    pub constants_buffer: Option<ConstantsBuffer>,
+
+    // Offscreen passes rendered before this one, in order.
+    pub buffers: Vec<Buffer>,
 }
 
 // Container for a shader program and its variables / render state.
 impl Program {
     pub fn new(
         device: Arc<wgpu::Device>,
+        queue: &wgpu::Queue,
         vertex: &wgpu::ShaderModule,
-        fragment: wgpu::ShaderModule,
-        reflect: spirv_reflect::ShaderModule,
-    ) -> Result<Self, String> {
-        // Keep track of the layout and
-        let mut uniform_groups: HashMap<u32, HashMap<u32, Uniform>> = HashMap::new();
-
-        // Enumerate the bind groups and individual bindings in the shader
-        for binding in reflect.enumerate_descriptor_bindings(Some("main")).unwrap() {
-
-            // If the binding isn't a uniform buffer, error out early to
-            // prevent locking up the driver when the shader tries to use it.
-            if binding.descriptor_type != ReflectDescriptorType::UniformBuffer {
-                return Err(format!(
-                    "Shader binding {} in set {} has unsupported type",
-                    binding.set, binding.binding
-                ));
-            }
+        fragment: Shader,
+        annotations: &Annotations,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, Error> {
+        // Errors from wgpu validating anything created along the way, like
+        // textures, bind groups, or a pipeline whose push constants don't
+        // match, go the same way as compile errors.
+        let scope = Arc::clone(&device);
+        error::capture(&scope, || Self::create(device, queue, vertex, fragment, annotations, format))
+            .map_err(Error::Pipeline)?
+    }
 
-            // Get the type description for this uniform's structure
-            let ty = binding.type_description.as_ref().ok_or_else(|| {
-                format!(
-                    "Failed to read type description for uniform {}",
-                    binding.name
-                )
-            })?;
-
-            // Keep track of the total size of all variables
-            let mut size = 0;
-            let mut vars = vec![];
-
-            for var in &ty.members {
-                // Make sure every variable's type is supported
-                let mut v = None;
-                if var.type_flags.contains(ReflectTypeFlags::VECTOR)
-                    && var.type_flags.contains(ReflectTypeFlags::FLOAT)
-                {
-                    v = match var.traits.numeric.vector.component_count {
-                        2 => Some(Variable::Vec2([1.0; 2])),
-                        3 => Some(Variable::Vec3([1.0; 3])),
-                        4 => Some(Variable::Vec4([1.0; 4])),
-                        _ => None,
-                    }
-                } else if var.type_flags == ReflectTypeFlags::FLOAT {
-                    v = match var.traits.numeric.scalar.width {
-                        32 => Some(Variable::Float(1.0)),
-                        _ => None,
-                    }
-                } else if var.type_flags == ReflectTypeFlags::INT {
-                    v = match var.traits.numeric.scalar.signedness {
-                        1 => Some(Variable::Int(1)),
-                        _ => None,
+    fn create(
+        device: Arc<wgpu::Device>,
+        queue: &wgpu::Queue,
+        vertex: &wgpu::ShaderModule,
+        fragment: Shader,
+        annotations: &Annotations,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, Error> {
+        // Includes can pin set numbers, so make sure they don't collide and
+        // that every set fits in the pipeline layout.
+        check_bindings(&fragment.bindings, device.limits().max_bind_groups)?;
+        let push = Constants::push(&device);
+        // Without push constants the constants take the set after the last
+        // one shaders can use.
+        if let Some(binding) = fragment.bindings.iter().find(|b| !push && b.set >= Constants::SET) {
+            return Err(Error::Pipeline(format!(
+                "\"{}\" uses set {}, but only sets below {} are available on devices without push constants",
+                binding.name,
+                binding.set,
+                Constants::SET
+            )));
+        }
+        let mut expected = fragment
+            .bindings
+            .iter()
+            .map(|b| (b.set, b.binding, b.name.clone(), b.resource.kind()))
+            .collect::<Vec<_>>();
+
+        // Keep track of the layout and resources of each bind group
+        let mut uniforms: HashMap<u32, HashMap<u32, Uniform>> = HashMap::new();
+        let mut textures: HashMap<u32, HashMap<u32, Texture>> = HashMap::new();
+        let mut samplers: HashMap<u32, HashMap<u32, Sampler>> = HashMap::new();
+
+        // Set up the resources for each binding in the shader
+        for binding in fragment.bindings {
+            match binding.resource {
+                Resource::Uniform(block) => {
+                    // Allocate a uniform buffer of the correct size
+                    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: None,
+                        size: block.size as u64,
+                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    });
+
+                    // Start variables out at their annotated defaults
+                    let mut vars = block.vars;
+                    let parent = &block.name;
+                    for (name, var) in &mut vars {
+                        annotations.apply_default(parent, name, var);
                     }
+                    let meta = vars.iter().map(|(name, _)| Meta::new(annotations, parent, name)).collect();
+
+                    let uniform = Uniform {
+                        name: block.name,
+                        vars,
+                        offsets: block.offsets,
+                        meta,
+                        size: block.size,
+                        mat2_stride: block.mat2_stride,
+                        buffer,
+                    };
+                    uniforms.entry(binding.set).or_default().insert(binding.binding, uniform);
                 }
-                let v = v.ok_or(format!(
-                    "Variable \"{}\" in uniform \"{}\" has unsupported type",
-                    &var.struct_member_name, &ty.type_name
-                ))?;
-
-                // Update, size, and align to a muliple of the size
-                size += v.size();
-                while size % v.size() != 0 {
-                    size += 1;
+                Resource::Texture => {
+                    // Load the image from an annotation if there is one, falling
+                    // back to a placeholder until one is picked in the GUI. Buffer
+                    // inputs are filled in with the buffer's contents when rendering.
+                    let path = annotations.get("", &binding.name, "image").and_then(|a| a.path(0));
+                    let texture = match path.filter(|_| buffer::input(&binding.name).is_none()) {
+                        Some(path) => Texture::load(&device, queue, &binding.name, &path)
+                            .unwrap_or_else(|e| {
+                                log::warn!("{}", e);
+                                Texture::placeholder(&device, queue, &binding.name)
+                            }),
+                        None => Texture::placeholder(&device, queue, &binding.name),
+                    };
+
+                    textures.entry(binding.set).or_default().insert(binding.binding, texture);
                 }
+                Resource::Sampler => {
+                    // Samplers split off from a combined sampler2D take the
+                    // annotations on its declaration.
+                    let get = |name| {
+                        annotations.get("", &binding.name, name).or_else(|| {
+                            annotations.get("", binding.name.strip_suffix("Sampler")?, name)
+                        })
+                    };
+                    let filter = get("filter")
+                        .and_then(|a| texture::filter_mode(a.args.first()?))
+                        .unwrap_or(wgpu::FilterMode::Linear);
+                    let wrap = get("wrap")
+                        .and_then(|a| texture::wrap_mode(a.args.first()?))
+                        .unwrap_or(wgpu::AddressMode::Repeat);
 
-                vars.push((var.struct_member_name.clone(), v));
+                    let sampler = Sampler::new(&device, &binding.name, filter, wrap);
+                    samplers.entry(binding.set).or_default().insert(binding.binding, sampler);
+                }
             }
-
-            // Allocate a uniform buffer of the correct size
-            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                label: None,
-                size: size as u64,
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
-
-            let group = uniform_groups
-                .entry(binding.set)
-                .or_default();
-
-            // Add the uniform to the bind group
-            group.insert(
-                binding.binding,
-                Uniform {
-                    name: ty.type_name.clone(),
-                    vars,
-                    buffer,
-                },
-            );
         }
 
-        // Create bind groups for each uniform group
-        let uniform_groups = uniform_groups
-            .into_iter()
-            .map(|(i, uniforms)| {
-                let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: None,
-                    entries: &uniforms
-                        .keys()
-                        .map(|j| wgpu::BindGroupLayoutEntry {
-                            binding: *j,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                min_binding_size: None,
-                                has_dynamic_offset: false,
-                            },
-                            count: None,
-                        })
-                        .collect::<Vec<_>>(),
-                });
-
-                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: None,
-                    layout: &bind_group_layout,
-                    entries: &uniforms
-                        .iter()
-                        .map(|(j, u)| wgpu::BindGroupEntry {
-                            binding: *j,
-                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                                buffer: &u.buffer,
-                                offset: 0,
-                                size: None,
-                            }),
-                        })
-                        .collect::<Vec<_>>(),
-                });
-
-                (i, UniformGroup {
-                    bind_group_layout,
-                    bind_group,
-                    uniforms,
-                })
+        // Create bind groups for every set up to the highest one used, leaving
+        // empty placeholders in any gaps so each lands at its own index. The
+        // constants come at `Constants::SET` after them when they're in a bind
+        // group of their own, since no set reaches it then.
+        let mut count = expected.iter().map(|b| b.0 + 1).max().unwrap_or(0);
+        let constants_buffer = if push {
+            None
+        } else {
+            count = Constants::SET;
+            Some(ConstantsBuffer::new(&device))
+        };
+        let uniform_groups = (0..count)
+            .map(|i| {
+                let group = UniformGroup::new(
+                    &device,
+                    uniforms.remove(&i).unwrap_or_default(),
+                    textures.remove(&i).unwrap_or_default(),
+                    samplers.remove(&i).unwrap_or_default(),
+                );
+                (i, group)
             })
             .collect::<HashMap<_, _>>();
 
-        // Create the pipeline
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &uniform_groups
-                .values()
-                .map(|g| &g.bind_group_layout)
-                .collect::<Vec<_>>(),
-            push_constant_ranges: &[wgpu::PushConstantRange {
+        // Create the pipeline, making sure each bind group layout lands at the
+        // index of the set the shader expects its resources in.
+        let mut layouts = (0..count)
+            .map(|i| &uniform_groups[&i])
+            .map(|g| (&g.bind_group_layout, g.layout_entries.as_slice()))
+            .collect::<Vec<_>>();
+        if let Some(c) = &constants_buffer {
+            layouts.push((&c.bind_group_layout, c.layout_entries.as_slice()));
+            expected.push((Constants::SET, 0, "constants".to_string(), "uniform block"));
+        }
+        check_layout(&layouts, &expected)?;
+        let push_constant_ranges = if push {
+            vec![wgpu::PushConstantRange {
                 stages: wgpu::ShaderStages::all(),
                 range: 0..Constants::SIZE,
-            }],
+            }]
+        } else {
+            vec![]
+        };
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &layouts.iter().map(|l| l.0).collect::<Vec<_>>(),
+            push_constant_ranges: &push_constant_ranges,
         });
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: None,
@@ -185,21 +233,65 @@ impl Program {
                 alpha_to_coverage_enabled: false,
             },
             fragment: Some(wgpu::FragmentState {
-                module: &fragment,
-                entry_point: "main",
-                targets: &[Some(crate::render::FORMAT.into())],
+                module: &fragment.module,
+                entry_point: &fragment.entry_point,
+                targets: &[Some(format.into())],
             }),
             multiview: None,
         });
 
         Ok(Self {
+            device,
             consts: Default::default(),
 
             pipeline,
             uniform_groups,
+            constants_buffer,
+
+            buffers: vec![],
         })
     }
 
+    // All uniform groups of the program and its buffer passes, along with the
+    // name of the buffer each belongs to.
+    pub fn groups(&self) -> impl Iterator<Item = (Option<&'static str>, &UniformGroup)> {
+        let own = self.uniform_groups.values().map(|g| (None, g));
+        let buffers = self.buffers.iter().flat_map(|b| {
+            let name = b.name;
+            b.program.uniform_groups.values().map(move |g| (Some(name), g))
+        });
+        own.chain(buffers)
+    }
+
+    pub fn groups_mut(&mut self) -> impl Iterator<Item = (Option<&'static str>, &mut UniformGroup)> {
+        let own = self.uniform_groups.values_mut().map(|g| (None, g));
+        let buffers = self.buffers.iter_mut().flat_map(|b| {
+            let name = b.name;
+            b.program.uniform_groups.values_mut().map(move |g| (Some(name), g))
+        });
+        own.chain(buffers)
+    }
+
+    // Point any textures reading from buffers at the latest contents of each buffer.
+    pub fn bind_buffers(&mut self, buffers: &[Texture]) {
+        for group in self.uniform_groups.values_mut() {
+            let mut changed = false;
+            for texture in group.textures.values_mut() {
+                if let Some(buffer) = buffer::input(&texture.name).and_then(|i| buffers.get(i)) {
+                    if !Arc::ptr_eq(&texture.view, &buffer.view) {
+                        texture.view = Arc::clone(&buffer.view);
+                        texture.size = buffer.size;
+                        changed = true;
+                    }
+                }
+            }
+
+            if changed {
+                group.bind(&self.device);
+            }
+        }
+    }
+
     // Initialize variables with the same name to the values from another program.
     // TODO: There's probably a cleaner way to do this...
     pub fn initialize(&mut self, other: &Program) {
@@ -207,47 +299,104 @@ impl Program {
             if let Some(ogroup) = other.uniform_groups.get(i) {
                 for (j, uniform) in &mut group.uniforms {
                     if let Some(ouniform) = ogroup.uniforms.get(j) {
-                        for (name, v) in &mut uniform.vars {
-                            for (oname, ov) in &ouniform.vars {
-                                if name == oname {
-                                    // Try to pick sane defaults, ex. convert int to float if type changed.
-                                    match v {
-                                        Variable::Int(i) => match ov {
-                                            Variable::Int(oi) => *i = *oi,
-                                            Variable::Float(of) => *i = *of as i32,
-                                            _ => {}
-                                        },
-                                        Variable::Float(f) => match ov {
-                                            Variable::Float(of) => *f = *of,
-                                            Variable::Int(oi) => *f = *oi as f32,
-                                            _ => {}
-                                        }
-                                        Variable::Vec2(a) => match ov {
-                                            Variable::Vec2(oa) => a.copy_from_slice(oa),
-                                            Variable::Vec3(oa) => a.copy_from_slice(&oa[..2]),
-                                            Variable::Vec4(oa) => a.copy_from_slice(&oa[..2]),
-                                            _ => {}
-                                        },
-                                        Variable::Vec3(a) => match ov {
-                                            Variable::Vec2(oa) => a.copy_from_slice(&[oa[0], oa[1], 0.0]),
-                                            Variable::Vec3(oa) => a.copy_from_slice(oa),
-                                            Variable::Vec4(oa) => a.copy_from_slice(&oa[..3]),
-                                            _ => {}
-                                        },
-                                        Variable::Vec4(a) => match ov {
-                                            Variable::Vec2(oa) => a.copy_from_slice(&[oa[0], oa[1], 0.0, 0.0]),
-                                            Variable::Vec3(oa) => a.copy_from_slice(&[oa[0], oa[1], oa[2], 0.0]),
-                                            Variable::Vec4(oa) => a.copy_from_slice(oa),
-                                            _ => {}
-                                        },
-                                    }
-                                }
-                            }
+                        // Try to pick sane defaults, ex. convert int to float if type changed.
+                        uniform::assign_vars(&mut uniform.vars, &ouniform.vars);
+                    }
+                }
+
+                // Keep images picked in the GUI unless the shader now names one itself,
+                // and keep sampler settings as they were.
+                for (j, texture) in &mut group.textures {
+                    if let Some(otexture) = ogroup.textures.get(j) {
+                        if texture.name == otexture.name && texture.path.is_none() {
+                            *texture = otexture.clone();
+                        }
+                    }
+                }
+                for (j, sampler) in &mut group.samplers {
+                    if let Some(osampler) = ogroup.samplers.get(j) {
+                        if sampler.name == osampler.name {
+                            *sampler = osampler.clone();
                         }
                     }
                 }
+                group.bind(&self.device);
             }
         }
 
+        for buffer in &mut self.buffers {
+            if let Some(obuffer) = other.buffers.iter().find(|b| b.name == buffer.name) {
+                buffer.initialize(obuffer);
+            }
+        }
+
+    }
+}
+
+// Check that no two resources in a shader share a binding, and that their sets
+// are within the device's limit.
+fn check_bindings(bindings: &[Binding], max_bind_groups: u32) -> Result<(), Error> {
+    let mut seen = HashMap::new();
+    for binding in bindings {
+        if binding.set >= max_bind_groups {
+            return Err(Error::Pipeline(format!(
+                "\"{}\" uses set {}, but only {} bind groups are supported",
+                binding.name, binding.set, max_bind_groups
+            )));
+        }
+        if let Some(other) = seen.insert((binding.set, binding.binding), &binding.name) {
+            return Err(Error::Pipeline(format!(
+                "\"{}\" and \"{}\" are both bound to set {} binding {}",
+                other, binding.name, binding.set, binding.binding
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Check that the bind group layout at each index of the pipeline layout holds
+// exactly the resources reflected for that set, as (set, binding, name, kind),
+// going by the entries each layout was created from.
+fn check_layout(
+    layouts: &[(&wgpu::BindGroupLayout, &[wgpu::BindGroupLayoutEntry])],
+    expected: &[(u32, u32, String, &str)],
+) -> Result<(), Error> {
+    let kind = |entry: &wgpu::BindGroupLayoutEntry| match entry.ty {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            ..
+        } => "uniform block",
+        wgpu::BindingType::Texture { .. } => "texture",
+        wgpu::BindingType::Sampler(_) => "sampler",
+        _ => "unsupported resource",
+    };
+
+    for (set, binding, name, expected) in expected {
+        let found = layouts
+            .get(*set as usize)
+            .and_then(|(_, entries)| entries.iter().find(|e| e.binding == *binding))
+            .map(kind);
+        if found != Some(*expected) {
+            return Err(Error::Pipeline(format!(
+                "Bind group layout doesn't match the shader: expected {} \"{}\" at set {} binding {}, found {}",
+                expected,
+                name,
+                set,
+                binding,
+                found.unwrap_or("nothing")
+            )));
+        }
+    }
+
+    for (set, (_, entries)) in layouts.iter().enumerate() {
+        let declared = expected.iter().filter(|b| b.0 == set as u32).count();
+        let bound = entries.len();
+        if declared != bound {
+            return Err(Error::Pipeline(format!(
+                "Bind group layout doesn't match the shader: set {} has {} bindings, expected {}",
+                set, bound, declared
+            )));
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,317 @@
+use crate::program::{expr, preset, Constants, Expr, Program, Variable};
+
+// Names for picking out the components of a vector.
+const COMPONENTS: [&str; 4] = ["x", "y", "z", "w"];
+
+// The shape of a low frequency oscillator's wave, each going between -1 and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Sine,
+    Saw,
+    Square,
+    Noise,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 4] = [Shape::Sine, Shape::Saw, Shape::Square, Shape::Noise];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Sine => "Sine",
+            Shape::Saw => "Saw",
+            Shape::Square => "Square",
+            Shape::Noise => "Noise",
+        }
+    }
+
+    // The wave at a phase in cycles.
+    fn wave(&self, phase: f64) -> f64 {
+        match self {
+            Shape::Sine => (phase * std::f64::consts::TAU).sin(),
+            Shape::Saw => expr::saw(phase),
+            Shape::Square => expr::square(phase),
+            Shape::Noise => expr::noise(phase),
+        }
+    }
+}
+
+// Where a modulated value comes from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    // Oscillates around `center` by `depth`, `rate` times a second, starting
+    // `phase` cycles in.
+    Lfo {
+        shape: Shape,
+        rate: f32,
+        depth: f32,
+        phase: f32,
+        center: f32,
+    },
+    // An expression as typed, along with the result of parsing it.
+    Expression {
+        text: String,
+        expr: Result<Expr, String>,
+    },
+}
+
+impl Source {
+    // A sine wave once a second around `center`.
+    pub fn lfo(center: f32) -> Self {
+        Source::Lfo {
+            shape: Shape::Sine,
+            rate: 1.0,
+            depth: 0.5,
+            phase: 0.0,
+            center,
+        }
+    }
+
+    pub fn expression(text: &str) -> Self {
+        Source::Expression {
+            text: text.to_string(),
+            expr: Expr::parse(text),
+        }
+    }
+}
+
+// Drives the value of a scalar variable, or one component of a vector, from a source.
+#[derive(Debug, Clone)]
+pub struct Modulator {
+    // The uniform block, keyed like in presets, and the top level variable in it.
+    pub block: String,
+    pub var: String,
+    pub component: Option<usize>,
+    pub source: Source,
+    // The error from the last evaluation, if it failed.
+    pub error: Option<String>,
+}
+
+impl Modulator {
+    pub fn new(block: &str, var: &str, component: Option<usize>, source: Source) -> Self {
+        Self {
+            block: block.to_string(),
+            var: var.to_string(),
+            component,
+            source,
+            error: None,
+        }
+    }
+
+    // Name of the modulated variable, ex. `Params.tint.x`.
+    pub fn target(&self) -> String {
+        name(&self.block, &self.var, self.component)
+    }
+}
+
+// Every modulator, along with the tempo for the `beat` value in expressions.
+#[derive(Debug, Clone)]
+pub struct Modulators {
+    pub list: Vec<Modulator>,
+    pub bpm: f32,
+}
+
+impl Default for Modulators {
+    fn default() -> Self {
+        Self {
+            list: vec![],
+            bpm: 120.0,
+        }
+    }
+}
+
+impl Modulators {
+    // Set the variables of a program from their modulators. Expressions can use
+    // the built-in constants, `beat` (the phase from 0 to 1 within the current
+    // beat) and `beats` (beats so far), and any scalar variable or component of
+    // a vector by name, ex. `speed` or `tint.x`, optionally prefixed with its
+    // block, ex. `Params.speed`. Variables are read as they were before any
+    // modulators this frame.
+    pub fn apply(&mut self, program: &mut Program, consts: &Constants) {
+        if self.list.is_empty() {
+            return;
+        }
+
+        let beats = consts.t as f64 * self.bpm as f64 / 60.0;
+        let values = {
+            let program = &*program;
+            let lookup = |name: &str| -> Option<f64> {
+                match name {
+                    "t" => Some(consts.t as f64),
+                    "dt" => Some(consts.dt as f64),
+                    "frame" => Some(consts.frame as f64),
+                    "aspect" => Some(consts.aspect as f64),
+                    "res.x" => Some(consts.resolution[0] as f64),
+                    "res.y" => Some(consts.resolution[1] as f64),
+                    "mpos.x" => Some(consts.mpos[0] as f64),
+                    "mpos.y" => Some(consts.mpos[1] as f64),
+                    "mclick.x" => Some(consts.mclick[0] as f64),
+                    "mclick.y" => Some(consts.mclick[1] as f64),
+                    "beat" => Some(beats - beats.floor()),
+                    "beats" => Some(beats),
+                    _ => value(program, name),
+                }
+            };
+
+            self.list
+                .iter()
+                .map(|m| match &m.source {
+                    Source::Lfo {
+                        shape,
+                        rate,
+                        depth,
+                        phase,
+                        center,
+                    } => {
+                        let p = consts.t as f64 * *rate as f64 + *phase as f64;
+                        Ok(*center as f64 + *depth as f64 * shape.wave(p))
+                    }
+                    Source::Expression { expr, .. } => expr.as_ref().map_err(Clone::clone)?.eval(&lookup),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (modulator, value) in self.list.iter_mut().zip(values) {
+            match value {
+                Ok(value) => {
+                    modulator.error = None;
+                    if let Some(var) = find_var(program, &modulator.block, &modulator.var) {
+                        set(var, modulator.component, value);
+                    }
+                }
+                Err(e) => modulator.error = Some(e),
+            }
+        }
+    }
+}
+
+// Every variable that can be modulated, as (block, variable, component).
+pub fn targets(program: &Program) -> Vec<(String, String, Option<usize>)> {
+    let mut targets = vec![];
+    for (pass, group) in program.groups() {
+        for uniform in group.uniforms.values() {
+            let block = preset::key(pass, &uniform.name);
+            for (name, var) in &uniform.vars {
+                if var.is_scalar() {
+                    targets.push((block.clone(), name.clone(), None));
+                } else if !var.is_matrix() && !matches!(var, Variable::Array(_) | Variable::Struct(_)) {
+                    for i in 0..var.components()[0].len() {
+                        targets.push((block.clone(), name.clone(), Some(i)));
+                    }
+                }
+            }
+        }
+    }
+    targets
+}
+
+// Name of a variable, or a component of a vector, ex. `Params.tint.x`.
+pub fn name(block: &str, var: &str, component: Option<usize>) -> String {
+    match component {
+        Some(i) => format!("{}.{}.{}", block, var, COMPONENTS[i]),
+        None => format!("{}.{}", block, var),
+    }
+}
+
+fn find_var<'a>(program: &'a mut Program, block: &str, var: &str) -> Option<&'a mut Variable> {
+    program
+        .groups_mut()
+        .flat_map(|(pass, group)| group.uniforms.values_mut().map(move |u| (pass, u)))
+        .find(|(pass, u)| preset::key(*pass, &u.name) == block)
+        .and_then(|(_, u)| u.vars.iter_mut().find(|(name, _)| name == var))
+        .map(|(_, v)| v)
+}
+
+// Look up a scalar variable, or a component of a vector, by name with an
+// optional block prefix, ex. `tint.x` or `Params.speed`.
+pub fn value(program: &Program, name: &str) -> Option<f64> {
+    resolve(program, name, None).or_else(|| {
+        let (path, c) = name.rsplit_once('.')?;
+        resolve(program, path, Some(COMPONENTS.iter().position(|x| *x == c)?))
+    })
+}
+
+fn resolve(program: &Program, path: &str, component: Option<usize>) -> Option<f64> {
+    let (block, var) = match path.rsplit_once('.') {
+        Some((block, var)) => (Some(block), var),
+        None => (None, path),
+    };
+
+    for (pass, group) in program.groups() {
+        for uniform in group.uniforms.values() {
+            if block.is_some_and(|b| b != preset::key(pass, &uniform.name)) {
+                continue;
+            }
+            if let Some((_, v)) = uniform.vars.iter().find(|(n, _)| n == var) {
+                if v.is_matrix() || (component.is_none() && !v.is_scalar()) {
+                    return None;
+                }
+                return v.components().first()?.get(component.unwrap_or(0)).copied();
+            }
+        }
+    }
+    None
+}
+
+// Set a scalar variable, or a component of a vector, converting to its type.
+fn set(var: &mut Variable, component: Option<usize>, value: f64) {
+    let mut c = var.components();
+    match c.first_mut().and_then(|col| col.get_mut(component.unwrap_or(0))) {
+        Some(x) if var.is_float() => *x = value,
+        Some(x) => *x = value.round(),
+        None => return,
+    }
+    var.set_components(&c);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn shapes() {
+        let at = |shape: Shape| [0.0, 0.25, 0.5].map(|phase| shape.wave(phase));
+
+        let [a, b, c] = at(Shape::Sine);
+        assert!(close(a, 0.0) && close(b, 1.0) && close(c, 0.0));
+        assert_eq!(at(Shape::Saw), [-1.0, -0.5, 0.0]);
+        assert_eq!(at(Shape::Square), [1.0, 1.0, -1.0]);
+        for x in at(Shape::Noise) {
+            assert!((-1.0..=1.0).contains(&x));
+        }
+        assert_eq!(at(Shape::Noise), at(Shape::Noise));
+
+        // Phases wrap around every cycle.
+        for shape in Shape::ALL.iter().filter(|s| **s != Shape::Noise) {
+            assert!(close(shape.wave(0.25), shape.wave(3.25)));
+        }
+    }
+
+    #[test]
+    fn set_components() {
+        let mut speed = Variable::Float(0.0);
+        set(&mut speed, None, 0.75);
+        assert!(matches!(speed, Variable::Float(x) if x == 0.75));
+
+        let mut count = Variable::Int(0);
+        set(&mut count, None, -2.6);
+        assert!(matches!(count, Variable::Int(-3)));
+
+        let mut tint = Variable::Vec3([0.0; 3]);
+        set(&mut tint, Some(1), 0.5);
+        assert!(matches!(tint, Variable::Vec3([0.0, y, 0.0]) if y == 0.5));
+
+        // Components past the end of the vector are ignored.
+        set(&mut tint, Some(3), 1.0);
+        assert!(matches!(tint, Variable::Vec3([0.0, y, 0.0]) if y == 0.5));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(name("Params", "speed", None), "Params.speed");
+        assert_eq!(name("Buffer A/Params", "tint", Some(2)), "Buffer A/Params.tint.z");
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use crate::program::{preset, Program, Variable};
+
+// An in-memory snapshot of the values of every uniform variable in a program
+// and its buffer passes, with blocks keyed like in presets.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub name: String,
+    blocks: HashMap<String, Vec<(String, Variable)>>,
+}
+
+impl Snapshot {
+    pub fn capture(name: &str, program: &Program) -> Self {
+        let mut blocks = HashMap::new();
+        for (pass, group) in program.groups() {
+            for uniform in group.uniforms.values() {
+                blocks.insert(preset::key(pass, &uniform.name), uniform.vars.clone());
+            }
+        }
+        Self {
+            name: name.to_string(),
+            blocks,
+        }
+    }
+}
+
+// Blends between a list of snapshots with a single crossfader. A position of
+// 0 is the first snapshot, 1 the second and so on, and positions in between
+// blend the snapshots on either side.
+#[derive(Debug, Clone, Default)]
+pub struct Morph {
+    pub snapshots: Vec<Snapshot>,
+    pub position: f32,
+}
+
+impl Morph {
+    // The position of the last snapshot.
+    pub fn end(&self) -> f32 {
+        self.snapshots.len().saturating_sub(1) as f32
+    }
+
+    // Set the variables in a program to the blend at `position`. Snapshot
+    // values are converted to the type of each variable first, the same way
+    // values carry over when a program is reloaded, so snapshots taken before
+    // the shader changed still blend. Variables missing from either snapshot
+    // are left as they are.
+    pub fn apply(&self, program: &mut Program, position: f32) {
+        let position = position.clamp(0.0, self.end());
+        let i = (position as usize).min(self.snapshots.len().saturating_sub(2));
+        let (a, b) = match (self.snapshots.get(i), self.snapshots.get(i + 1)) {
+            (Some(a), Some(b)) => (a, b),
+            (Some(a), None) => (a, a),
+            _ => return,
+        };
+        let t = (position - i as f32) as f64;
+
+        for (pass, group) in program.groups_mut() {
+            for uniform in group.uniforms.values_mut() {
+                let key = preset::key(pass, &uniform.name);
+                if let (Some(a), Some(b)) = (a.blocks.get(&key), b.blocks.get(&key)) {
+                    mix_vars(&mut uniform.vars, a, b, t);
+                }
+            }
+        }
+    }
+}
+
+fn mix_vars(vars: &mut [(String, Variable)], a: &[(String, Variable)], b: &[(String, Variable)], t: f64) {
+    let find = |vars: &'_ [(String, Variable)], name: &str| {
+        vars.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone())
+    };
+    for (name, var) in vars {
+        if let (Some(a), Some(b)) = (find(a, name), find(b, name)) {
+            let mut from = var.clone();
+            from.assign(&a);
+            let mut to = var.clone();
+            to.assign(&b);
+
+            from.mix(&to, t);
+            *var = from;
+        }
+    }
+}
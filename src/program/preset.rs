@@ -0,0 +1,222 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::program::{Program, Variable};
+
+// A snapshot of the values of every uniform variable in a program and its
+// buffer passes, stored as a JSON object like:
+//
+// {
+//     "Params": { "speed": 1.5, "tint": [1, 0.5, 0], "rot": [[1, 0], [0, 1]] },
+//     "Buffer A/Params": { "decay": 0.9 }
+// }
+//
+// Blocks are keyed by name, prefixed with the buffer they belong to if any,
+// and variables within them by member name. Matrices are lists of columns.
+#[derive(Clone)]
+pub struct Preset(Map<String, Value>);
+
+impl Preset {
+    pub fn capture(program: &Program) -> Self {
+        let mut blocks = Map::new();
+        for (pass, group) in program.groups() {
+            for uniform in group.uniforms.values() {
+                blocks.insert(key(pass, &uniform.name), vars_to_json(&uniform.vars));
+            }
+        }
+        Self(blocks)
+    }
+
+    // Set variables in the program to the values in the preset. Variables that
+    // are missing from the preset or whose type doesn't match are left as is.
+    pub fn apply(&self, program: &mut Program) {
+        for (pass, group) in program.groups_mut() {
+            for uniform in group.uniforms.values_mut() {
+                if let Some(Value::Object(values)) = self.0.get(&key(pass, &uniform.name)) {
+                    apply_vars(&mut uniform.vars, values);
+                }
+            }
+        }
+    }
+
+    pub fn load(file: &Path) -> Result<Self, String> {
+        let err = |e: String| format!("Failed to load preset \"{}\": {}", file.display(), e);
+
+        let content = std::fs::read_to_string(file).map_err(|e| err(e.to_string()))?;
+        let value = serde_json::from_str(&content).map_err(|e| err(e.to_string()))?;
+        Self::from_json(value).map_err(err)
+    }
+
+    // Find a preset for a shader file by name, either in the shader's presets
+    // file or, if the name is a path to a `.json` file, in that file.
+    pub fn find(shader: &Path, name: &str) -> Result<Self, String> {
+        let path = Path::new(name);
+        if path.extension().is_some_and(|e| e == "json") {
+            return Self::load(path);
+        }
+
+        let presets = Presets::load(shader)?;
+        presets.get(name).ok_or_else(|| {
+            format!("No preset named \"{}\" in \"{}\"", name, presets.file.display())
+        })
+    }
+
+    fn from_json(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Object(blocks) => Ok(Self(blocks)),
+            _ => Err("expected an object of uniform blocks".to_string()),
+        }
+    }
+}
+
+// Named presets for a shader file, kept in a file next to it named after it,
+// ex. `demo.presets.json` for `demo.glsl`, which maps names to presets:
+//
+// {
+//     "calm": { "Params": { "speed": 0.5 } },
+//     "wild": { "Params": { "speed": 4 } }
+// }
+pub struct Presets {
+    file: PathBuf,
+    presets: Map<String, Value>,
+}
+
+impl Presets {
+    // No presets for a shader file, which are saved to its presets file.
+    pub fn empty(shader: &Path) -> Self {
+        Self {
+            file: shader.with_extension("presets.json"),
+            presets: Map::new(),
+        }
+    }
+
+    // Load the presets for a shader file, or none if it has no presets file yet.
+    pub fn load(shader: &Path) -> Result<Self, String> {
+        let file = shader.with_extension("presets.json");
+        let err = |e: String| format!("Failed to load presets \"{}\": {}", file.display(), e);
+
+        let presets = match std::fs::read_to_string(&file) {
+            Ok(content) => match serde_json::from_str(&content).map_err(|e| err(e.to_string()))? {
+                Value::Object(presets) => presets,
+                _ => return Err(err("expected an object of presets".to_string())),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Map::new(),
+            Err(e) => return Err(err(e.to_string())),
+        };
+
+        Ok(Self { file, presets })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(&self.presets).unwrap();
+        std::fs::write(&self.file, content)
+            .map_err(|e| format!("Failed to save presets \"{}\": {}", self.file.display(), e))
+    }
+
+    // Names of the presets in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.presets.keys().map(String::as_str)
+    }
+
+    // Get a preset by name. Presets that aren't objects of blocks are ignored.
+    pub fn get(&self, name: &str) -> Option<Preset> {
+        Preset::from_json(self.presets.get(name)?.clone()).ok()
+    }
+
+    pub fn insert(&mut self, name: &str, preset: Preset) {
+        self.presets.insert(name.to_string(), Value::Object(preset.0));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.presets.remove(name);
+    }
+}
+
+// Key a uniform block by name, prefixed with the buffer pass it's in if any.
+pub fn key(pass: Option<&str>, name: &str) -> String {
+    match pass {
+        Some(pass) => format!("Buffer {}/{}", pass, name),
+        None => name.to_string(),
+    }
+}
+
+fn vars_to_json(vars: &[(String, Variable)]) -> Value {
+    Value::Object(vars.iter().map(|(name, v)| (name.clone(), to_json(v))).collect())
+}
+
+pub fn to_json(var: &Variable) -> Value {
+    let float = var.is_float();
+    let number = |x: f64| {
+        if float {
+            // Go through the shortest string that round trips as an f32 so
+            // values like 0.1 don't show up as 0.10000000149011612.
+            let x: f64 = (x as f32).to_string().parse().unwrap();
+            serde_json::Number::from_f64(x).map_or(Value::Null, Value::Number)
+        } else {
+            Value::from(x as i64)
+        }
+    };
+    let list = |c: &[f64]| Value::Array(c.iter().map(|&x| number(x)).collect());
+
+    match var {
+        Variable::Bool(b) => Value::Bool(*b),
+        Variable::Array(a) => Value::Array(a.elems.iter().map(to_json).collect()),
+        Variable::Struct(s) => vars_to_json(&s.vars),
+        _ if var.is_scalar() => number(var.components()[0][0]),
+        _ if var.is_matrix() => Value::Array(var.components().iter().map(|c| list(c)).collect()),
+        _ => list(&var.components()[0]),
+    }
+}
+
+fn apply_vars(vars: &mut [(String, Variable)], values: &Map<String, Value>) {
+    for (name, v) in vars {
+        if let Some(value) = values.get(name) {
+            assign_json(v, value);
+        }
+    }
+}
+
+// Set a variable to a JSON value, if it has the same shape.
+pub fn assign_json(var: &mut Variable, value: &Value) {
+    match (&mut *var, value) {
+        (Variable::Array(a), Value::Array(values)) => {
+            for (e, value) in a.elems.iter_mut().zip(values) {
+                assign_json(e, value);
+            }
+        }
+        (Variable::Struct(s), Value::Object(values)) => apply_vars(&mut s.vars, values),
+        _ => {
+            // Only take values of the same shape, ex. no lists for scalars.
+            let fits = match value {
+                Value::Array(c) if c.first().is_some_and(Value::is_array) => var.is_matrix(),
+                Value::Array(_) => !var.is_matrix() && !var.is_scalar(),
+                _ => var.is_scalar(),
+            };
+            if let Some(c) = components(value).filter(|_| fits) {
+                var.set_components(&c);
+            }
+        }
+    }
+}
+
+// Read a scalar, a list of scalars, or a list of lists of scalars into the
+// same form as `Variable::components`.
+fn components(value: &Value) -> Option<Vec<Vec<f64>>> {
+    fn scalar(value: &Value) -> Option<f64> {
+        match value {
+            Value::Bool(b) => Some(*b as u32 as f64),
+            Value::Number(n) => n.as_f64(),
+            _ => None,
+        }
+    }
+    fn list(value: &Value) -> Option<Vec<f64>> {
+        value.as_array()?.iter().map(scalar).collect()
+    }
+
+    match value {
+        Value::Array(c) if c.first().is_some_and(Value::is_array) => c.iter().map(list).collect(),
+        Value::Array(_) => Some(vec![list(value)?]),
+        _ => Some(vec![vec![scalar(value)?]]),
+    }
+}
@@ -0,0 +1,262 @@
+use crate::program::{Constants, Struct, Variable};
+
+pub mod spirv;
+pub mod wgsl;
+
+// A resource bound by a shader, reflected from either SPIR-V or naga IR.
+#[derive(Debug)]
+pub struct Binding {
+    pub set: u32,
+    pub binding: u32,
+    pub name: String,
+    pub resource: Resource,
+}
+
+#[derive(Debug)]
+pub enum Resource {
+    // A uniform block, sized to fit the whole buffer backing it.
+    Uniform(Struct),
+    // A 2D texture of floats, the only kind supported.
+    Texture,
+    Sampler,
+}
+
+impl Resource {
+    // What kind of resource this is, for messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Resource::Uniform(_) => "uniform block",
+            Resource::Texture => "texture",
+            Resource::Sampler => "sampler",
+        }
+    }
+}
+
+// The members of the push constant block the built-in constants are written
+// to, as (name, offset, type). Shaders can leave members off the end.
+pub const CONSTANTS: [(&str, u32, &str); 8] = [
+    ("t", 0, "float"),
+    ("res", 8, "vec2"),
+    ("aspect", 16, "float"),
+    ("mpos", 24, "vec2"),
+    ("mclick", 32, "vec2"),
+    ("dt", 40, "float"),
+    ("frame", 44, "int"),
+    ("date", 48, "vec4"),
+];
+
+// Compare a shader's push constant block with the built-in constants, giving
+// (member, message) for each member that would get the wrong value. Members
+// that are only named differently still get the right value, so they're just
+// warned about.
+pub fn check_constants(block: &Struct) -> Vec<(String, String)> {
+    // The frame number is written as a uint, but never gets big enough for
+    // reading it as an int to matter.
+    let same_type = |a: &str, b: &str| a == b || (a == "int" && b == "uint");
+
+    let mut errors = vec![];
+    for ((name, var), offset) in block.vars.iter().zip(&block.offsets) {
+        let ty = var.type_name();
+        match CONSTANTS.iter().find(|c| c.0 == name) {
+            Some((_, o, t)) if o == offset && same_type(t, &ty) => {}
+            Some((_, o, t)) => errors.push((
+                name.clone(),
+                format!(
+                    "Constant \"{}\" is a {} at offset {}, but should be a {} at offset {}",
+                    name, ty, offset, t, o
+                ),
+            )),
+            None => match CONSTANTS.iter().find(|c| c.1 == *offset && same_type(c.2, &ty)) {
+                Some(c) => log::warn!("Constant \"{}\" gets the value of \"{}\"", name, c.0),
+                None => errors.push((
+                    name.clone(),
+                    format!(
+                        "Unknown constant \"{}\" ({} at offset {}), expected one of {}",
+                        name,
+                        ty,
+                        offset,
+                        CONSTANTS
+                            .iter()
+                            .map(|(n, o, t)| format!("{} {} at {}", t, n, o))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                )),
+            },
+        }
+    }
+    errors
+}
+
+// Take the uniform block holding the constants on devices without push
+// constants out of a shader's bindings, see `Constants::SET`.
+pub fn take_constants(bindings: &mut Vec<Binding>) -> Option<Struct> {
+    let i = bindings.iter().position(|b| {
+        b.set == Constants::SET && b.binding == 0 && matches!(b.resource, Resource::Uniform(_))
+    })?;
+    match bindings.remove(i).resource {
+        Resource::Uniform(block) => Some(block),
+        _ => None,
+    }
+}
+
+// The type of the components of a scalar, vector or matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scalar {
+    Bool,
+    Int,
+    UInt,
+    Float,
+}
+
+// Create a scalar (1x1), vector (1xN) or square matrix (NxN) variable with a
+// default value, if the type is supported. Numbers start out as 1 and matrices
+// as the identity.
+pub fn value(scalar: Scalar, columns: u32, rows: u32) -> Option<Variable> {
+    match (scalar, columns, rows) {
+        (Scalar::Bool, 1, 1) => Some(Variable::Bool(true)),
+        (Scalar::Int, 1, 1) => Some(Variable::Int(1)),
+        (Scalar::UInt, 1, 1) => Some(Variable::UInt(1)),
+        (Scalar::Float, 1, 1) => Some(Variable::Float(1.0)),
+        (Scalar::Float, 1, 2) => Some(Variable::Vec2([1.0; 2])),
+        (Scalar::Float, 1, 3) => Some(Variable::Vec3([1.0; 3])),
+        (Scalar::Float, 1, 4) => Some(Variable::Vec4([1.0; 4])),
+        (Scalar::Int, 1, 2) => Some(Variable::IVec2([1; 2])),
+        (Scalar::Int, 1, 3) => Some(Variable::IVec3([1; 3])),
+        (Scalar::Int, 1, 4) => Some(Variable::IVec4([1; 4])),
+        (Scalar::UInt, 1, 2) => Some(Variable::UVec2([1; 2])),
+        (Scalar::UInt, 1, 3) => Some(Variable::UVec3([1; 3])),
+        (Scalar::UInt, 1, 4) => Some(Variable::UVec4([1; 4])),
+        (Scalar::Float, 2, 2) => Some(Variable::Mat2([[1.0, 0.0], [0.0, 1.0]])),
+        (Scalar::Float, 3, 3) => Some(Variable::Mat3([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])),
+        (Scalar::Float, 4, 4) => Some(Variable::Mat4([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Build a block the way reflection does, with the offsets the shader gave
+    // each member rather than ones computed from the layout rules.
+    fn block(members: &[(&str, Scalar, u32, u32)]) -> Struct {
+        let vars: Vec<_> = members
+            .iter()
+            .map(|&(name, scalar, rows, _)| (name.to_string(), value(scalar, 1, rows).unwrap()))
+            .collect();
+        let offsets: Vec<_> = members.iter().map(|m| m.3).collect();
+        let size = offsets.last().unwrap() + vars.last().unwrap().1.size();
+        Struct {
+            name: "Constants".to_string(),
+            vars,
+            offsets,
+            size,
+            mat2_stride: 16,
+        }
+    }
+
+    fn constants() -> Vec<(&'static str, Scalar, u32, u32)> {
+        vec![
+            ("t", Scalar::Float, 1, 0),
+            ("res", Scalar::Float, 2, 8),
+            ("aspect", Scalar::Float, 1, 16),
+            ("mpos", Scalar::Float, 2, 24),
+            ("mclick", Scalar::Float, 2, 32),
+            ("dt", Scalar::Float, 1, 40),
+            ("frame", Scalar::Int, 1, 44),
+            ("date", Scalar::Float, 4, 48),
+        ]
+    }
+
+    fn names(errors: &[(String, String)]) -> Vec<&str> {
+        errors.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn matching_block() {
+        assert!(check_constants(&block(&constants())).is_empty());
+    }
+
+    #[test]
+    fn uint_frame() {
+        let mut members = constants();
+        members[6].1 = Scalar::UInt;
+        assert!(check_constants(&block(&members)).is_empty());
+    }
+
+    #[test]
+    fn block_cut_short() {
+        assert!(check_constants(&block(&constants()[..3])).is_empty());
+        assert!(check_constants(&block(&constants()[..1])).is_empty());
+    }
+
+    #[test]
+    fn wrong_type() {
+        let members = [
+            ("t", Scalar::Float, 1, 0),
+            ("res", Scalar::Float, 3, 16),
+            ("aspect", Scalar::Float, 1, 28),
+        ];
+        let errors = check_constants(&block(&members));
+        assert_eq!(names(&errors), ["res", "aspect"]);
+        assert!(errors[0].1.contains("vec3"));
+    }
+
+    #[test]
+    fn wrong_offset() {
+        let members = [("t", Scalar::Float, 1, 0), ("aspect", Scalar::Float, 1, 4)];
+        let errors = check_constants(&block(&members));
+        assert_eq!(names(&errors), ["aspect"]);
+        assert!(errors[0].1.contains("offset 16"));
+    }
+
+    #[test]
+    fn renamed_member() {
+        let members = [("time", Scalar::Float, 1, 0), ("resolution", Scalar::Float, 2, 8)];
+        assert!(check_constants(&block(&members)).is_empty());
+    }
+
+    #[test]
+    fn unknown_member() {
+        let members = [("t", Scalar::Float, 1, 0), ("speed", Scalar::Float, 1, 4)];
+        let errors = check_constants(&block(&members));
+        assert_eq!(names(&errors), ["speed"]);
+    }
+
+    #[test]
+    fn reflected_constants() {
+        let source = "#version 450
+            layout(location = 0) out vec4 color;
+            layout(push_constant) uniform Constants {
+                float t;
+                vec2 res;
+                float aspect;
+                vec2 mpos;
+                vec2 mclick;
+                float dt;
+                int frame;
+                vec4 date;
+            } c;
+            void main() {
+                color = vec4(c.t, c.res, c.aspect) + vec4(c.mpos, c.mclick)
+                    + vec4(c.dt, c.frame, 0, 0) + c.date;
+            }";
+        let mut compiler = shaderc::Compiler::new().unwrap();
+        let spirv = compiler
+            .compile_into_spirv(source, shaderc::ShaderKind::Fragment, "test.glsl", "main", None)
+            .unwrap();
+        let module = spirv_reflect::create_shader_module(spirv.as_binary_u8()).unwrap();
+        let block = spirv::push_constants(&module, "main").unwrap().unwrap();
+
+        let reflected: Vec<_> = block.vars.iter().map(|(name, _)| name.as_str()).zip(&block.offsets).collect();
+        let expected: Vec<_> = CONSTANTS.iter().map(|(name, offset, _)| (*name, offset)).collect();
+        assert_eq!(reflected, expected);
+        assert!(check_constants(&block).is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+use spirv_reflect::types::{
+    ReflectBlockVariable, ReflectDescriptorBinding, ReflectDescriptorType, ReflectDimension,
+    ReflectTypeDescription, ReflectTypeFlags,
+};
+
+use crate::program::{
+    layout,
+    reflect::{value, Binding, Resource, Scalar},
+    Array, Error, Struct, Variable,
+};
+
+// Reflect the resources bound by an entry point of a SPIR-V module.
+pub fn bindings(module: &spirv_reflect::ShaderModule, entry_point: &str) -> Result<Vec<Binding>, Error> {
+    module
+        .enumerate_descriptor_bindings(Some(entry_point))
+        .map_err(|e| Error::Reflect {
+            message: e.to_string(),
+            file: None,
+            line: None,
+        })?
+        .iter()
+        .map(|binding| {
+            let resource = resource(binding).map_err(|message| Error::Unsupported {
+                set: binding.set,
+                binding: binding.binding,
+                name: binding.name.clone(),
+                message,
+                file: None,
+                line: None,
+            })?;
+            Ok(Binding {
+                set: binding.set,
+                binding: binding.binding,
+                name: binding.name.clone(),
+                resource,
+            })
+        })
+        .collect()
+}
+
+// Reflect the push constant block used by an entry point, if there is one.
+pub fn push_constants(module: &spirv_reflect::ShaderModule, entry_point: &str) -> Result<Option<Struct>, String> {
+    let blocks = module.enumerate_push_constant_blocks(Some(entry_point))?;
+    let block = match blocks.first() {
+        Some(block) => block,
+        None => return Ok(None),
+    };
+    let ty = block
+        .type_description
+        .as_ref()
+        .ok_or("Failed to read type description for push constants")?;
+    structure(ty, block).map(Some)
+}
+
+fn resource(binding: &ReflectDescriptorBinding) -> Result<Resource, String> {
+    match binding.descriptor_type {
+        ReflectDescriptorType::UniformBuffer => uniform(binding).map(Resource::Uniform),
+        ReflectDescriptorType::SampledImage => {
+            let image = &binding.image;
+            if image.dim != ReflectDimension::Type2d || image.arrayed != 0 || image.ms != 0 {
+                return Err(format!(
+                    "Texture \"{}\" is not a 2D texture, which is the only kind supported",
+                    binding.name
+                ));
+            }
+            Ok(Resource::Texture)
+        }
+        ReflectDescriptorType::Sampler => Ok(Resource::Sampler),
+        ReflectDescriptorType::CombinedImageSampler => Err(format!(
+            "Combined image sampler \"{}\" is only supported when declared on its own \
+             line with a set and binding in the file being compiled, declare a separate \
+             texture2D and sampler and sample with sampler2D(tex, samp) instead",
+            binding.name
+        )),
+        // If the binding isn't otherwise supported, error out early to
+        // prevent locking up the driver when the shader tries to use it.
+        _ => Err(format!("Shader binding \"{}\" has unsupported type", binding.name)),
+    }
+}
+
+fn uniform(binding: &ReflectDescriptorBinding) -> Result<Struct, String> {
+    // Get the type description for this uniform's structure
+    let ty = binding.type_description.as_ref().ok_or_else(|| {
+        format!(
+            "Failed to read type description for uniform {}",
+            binding.name
+        )
+    })?;
+
+    let block = structure(ty, &binding.block)?;
+
+    // The reflected layout is authoritative, but a disagreement with std140
+    // usually means the shader declared an explicit layout or packing.
+    if let Err(e) = layout::validate(&block.vars, &block.offsets) {
+        log::warn!("Uniform \"{}\": {}", &ty.type_name, e);
+    }
+
+    // Size the buffer to fit the whole block, including any trailing padding,
+    // rounded up so that it can always be written to in full.
+    let size = layout::round_up(
+        binding.block.padded_size.max(binding.block.size).max(block.size),
+        16,
+    );
+
+    Ok(Struct { size, ..block })
+}
+
+// Create variables with default values for each member of a reflected struct,
+// along with their offsets within it.
+fn structure(ty: &ReflectTypeDescription, block: &ReflectBlockVariable) -> Result<Struct, String> {
+    let mut vars = vec![];
+    let mut offsets = vec![];
+
+    for (i, var) in ty.members.iter().enumerate() {
+        // Take the offset straight from the reflected block so the data
+        // ends up exactly where the shader expects it.
+        let member = block.members.get(i).ok_or_else(|| {
+            format!(
+                "Failed to read layout of variable \"{}\" in \"{}\"",
+                &var.struct_member_name, &ty.type_name
+            )
+        })?;
+
+        // Make sure every variable's type is supported
+        let v = variable(&ty.type_name, var, member)?;
+
+        vars.push((var.struct_member_name.clone(), v));
+        offsets.push(member.offset);
+    }
+
+    // Pad the struct out to a multiple of a vec4, as std140 requires.
+    let end = vars
+        .iter()
+        .zip(&offsets)
+        .map(|((_, v), offset)| offset + v.size())
+        .max()
+        .unwrap_or(0);
+
+    Ok(Struct {
+        name: ty.type_name.clone(),
+        vars,
+        offsets,
+        size: layout::round_up(end, 16),
+        mat2_stride: 16,
+    })
+}
+
+// Create a variable with a default value from its reflected type and layout,
+// if the type is supported. `parent` is the name of the enclosing struct.
+fn variable(
+    parent: &str,
+    ty: &ReflectTypeDescription,
+    block: &ReflectBlockVariable,
+) -> Result<Variable, String> {
+    let dims = &ty.traits.array.dims;
+    if ty.type_flags.contains(ReflectTypeFlags::ARRAY) && !dims.is_empty() {
+        array(parent, ty, block, dims, block.array.stride)
+    } else if ty.type_flags.contains(ReflectTypeFlags::STRUCT) {
+        structure(ty, block).map(Variable::Struct)
+    } else {
+        numeric(ty).ok_or(format!(
+            "Variable \"{}\" in \"{}\" has unsupported type",
+            &ty.struct_member_name, parent
+        ))
+    }
+}
+
+// Create an array variable, where `dims` are the remaining dimensions of a
+// possibly multidimensional array and `stride` is the stride of the outermost.
+fn array(
+    parent: &str,
+    ty: &ReflectTypeDescription,
+    block: &ReflectBlockVariable,
+    dims: &[u32],
+    stride: u32,
+) -> Result<Variable, String> {
+    let (len, inner) = (dims[0], &dims[1..]);
+
+    // Runtime sized arrays can only appear in storage buffers.
+    if len == 0 || stride == 0 {
+        return Err(format!(
+            "Array \"{}\" in \"{}\" has no fixed size",
+            &ty.struct_member_name, parent
+        ));
+    }
+
+    let elems = (0..len)
+        .map(|_| match inner.first() {
+            // Inner arrays are packed tightly inside each element of the outer one.
+            Some(n) => array(parent, ty, block, inner, stride / n),
+            None => {
+                let mut elem = ty.clone();
+                elem.type_flags.remove(ReflectTypeFlags::ARRAY);
+                elem.traits.array.dims.clear();
+                variable(parent, &elem, block)
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Variable::Array(Array { stride, elems }))
+}
+
+// Create a scalar, vector or matrix variable with a default value from its
+// reflected type, if the type is supported.
+fn numeric(ty: &ReflectTypeDescription) -> Option<Variable> {
+    let flags = ty.type_flags;
+    if flags == ReflectTypeFlags::BOOL {
+        return value(Scalar::Bool, 1, 1);
+    }
+
+    let numeric = &ty.traits.numeric;
+    if numeric.scalar.width != 32 {
+        return None;
+    }
+
+    let scalar = if flags.contains(ReflectTypeFlags::FLOAT) {
+        Scalar::Float
+    } else if flags.contains(ReflectTypeFlags::INT) && numeric.scalar.signedness == 1 {
+        Scalar::Int
+    } else if flags.contains(ReflectTypeFlags::INT) {
+        Scalar::UInt
+    } else {
+        return None;
+    };
+
+    let shape = flags - ReflectTypeFlags::FLOAT - ReflectTypeFlags::INT;
+    if shape == ReflectTypeFlags::MATRIX | ReflectTypeFlags::VECTOR {
+        value(scalar, numeric.matrix.column_count, numeric.matrix.row_count)
+    } else if shape == ReflectTypeFlags::VECTOR {
+        value(scalar, 1, numeric.vector.component_count)
+    } else if shape.is_empty() {
+        value(scalar, 1, 1)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,226 @@
+use naga::{
+    AddressSpace, ArraySize, GlobalVariable, Handle, ImageClass, ImageDimension, Module, ResourceBinding,
+    ScalarKind, StructMember, Type, TypeInner,
+};
+
+use crate::program::{
+    layout,
+    reflect::{value, Binding, Resource, Scalar},
+    Array, Constants, Error, Struct, Variable,
+};
+
+// Reflect the resources bound by a module parsed from WGSL. Offsets come from
+// naga's layout of the module, which follows WGSL's rules for uniform buffers.
+pub fn bindings(module: &Module) -> Result<Vec<Binding>, Error> {
+    module
+        .global_variables
+        .iter()
+        .filter_map(|(_, var)| Some((var, var.binding.as_ref()?)))
+        .map(|(var, binding)| {
+            let name = var.name.clone().unwrap_or_default();
+            let resource = resource(module, var, &name).map_err(|message| Error::Unsupported {
+                set: binding.group,
+                binding: binding.binding,
+                name: name.clone(),
+                message,
+                file: None,
+                line: None,
+            })?;
+
+            Ok(Binding {
+                set: binding.group,
+                binding: binding.binding,
+                name,
+                resource,
+            })
+        })
+        .collect()
+}
+
+// Reflect the push constant struct of a module, if there is one.
+pub fn push_constants(module: &Module) -> Result<Option<Struct>, String> {
+    module
+        .global_variables
+        .iter()
+        .find(|(_, var)| var.space == AddressSpace::PushConstant)
+        .map(|(_, var)| {
+            let ty = &module.types[var.ty];
+            match &ty.inner {
+                TypeInner::Struct { members, span } => structure(module, ty, members, *span),
+                _ => Err("Push constants must be a struct".to_string()),
+            }
+        })
+        .transpose()
+}
+
+// Move the push constants of a module into a uniform buffer at binding 0 of
+// `Constants::SET`, for devices without push constants. This works on the IR
+// rather than the source so that any spelling of the declaration is found.
+pub fn uniform_constants(module: &mut Module) {
+    for (_, var) in module.global_variables.iter_mut() {
+        if var.space == AddressSpace::PushConstant {
+            var.space = AddressSpace::Uniform;
+            var.binding = Some(ResourceBinding {
+                group: Constants::SET,
+                binding: 0,
+            });
+        }
+    }
+}
+
+fn resource(module: &Module, var: &GlobalVariable, name: &str) -> Result<Resource, String> {
+    let ty = &module.types[var.ty];
+    match (var.space, &ty.inner) {
+        (AddressSpace::Uniform, TypeInner::Struct { members, span }) => {
+            let block = structure(module, ty, members, *span)?;
+            // Round the buffer up so that it can always be written to in full.
+            let size = layout::round_up(block.size, 16);
+            Ok(Resource::Uniform(Struct { size, ..block }))
+        }
+        (AddressSpace::Uniform, _) => Err(format!(
+            "Uniform \"{}\" is not a struct, which is the only kind supported",
+            name
+        )),
+        (
+            AddressSpace::Handle,
+            TypeInner::Image {
+                dim: ImageDimension::D2,
+                arrayed: false,
+                class: ImageClass::Sampled { kind: ScalarKind::Float, multi: false },
+            },
+        ) => Ok(Resource::Texture),
+        (AddressSpace::Handle, TypeInner::Image { .. }) => Err(format!(
+            "Texture \"{}\" is not a texture_2d<f32>, which is the only kind supported",
+            name
+        )),
+        (AddressSpace::Handle, TypeInner::Sampler { comparison: false }) => Ok(Resource::Sampler),
+        _ => Err(format!("Shader binding \"{}\" has unsupported type", name)),
+    }
+}
+
+// Create variables with default values for each member of a struct, along with
+// their offsets within it.
+fn structure(module: &Module, ty: &Type, members: &[StructMember], span: u32) -> Result<Struct, String> {
+    let name = ty.name.clone().unwrap_or_default();
+
+    let mut vars = vec![];
+    let mut offsets = vec![];
+    for member in members {
+        let member_name = member.name.clone().unwrap_or_default();
+        vars.push((member_name.clone(), variable(module, &name, &member_name, member.ty)?));
+        offsets.push(member.offset);
+    }
+
+    Ok(Struct {
+        name,
+        vars,
+        offsets,
+        size: span,
+        // Unlike std140, WGSL packs the columns of a mat2x2 tightly.
+        mat2_stride: 8,
+    })
+}
+
+// Create a variable with a default value from its type, if the type is
+// supported. `parent` is the name of the enclosing struct.
+fn variable(module: &Module, parent: &str, name: &str, ty: Handle<Type>) -> Result<Variable, String> {
+    let unsupported = || format!("Variable \"{}\" in \"{}\" has unsupported type", name, parent);
+
+    let ty = &module.types[ty];
+    match ty.inner {
+        TypeInner::Scalar { kind, width: 4 } => value(scalar(kind), 1, 1).ok_or_else(unsupported),
+        TypeInner::Vector { size, kind, width: 4 } => {
+            value(scalar(kind), 1, size as u32).ok_or_else(unsupported)
+        }
+        TypeInner::Matrix { columns, rows, width: 4 } => {
+            value(Scalar::Float, columns as u32, rows as u32).ok_or_else(unsupported)
+        }
+        TypeInner::Array {
+            base,
+            size: ArraySize::Constant(len),
+            stride,
+        } => {
+            let elems = (0..len.get())
+                .map(|_| variable(module, parent, name, base))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Variable::Array(Array { stride, elems }))
+        }
+        TypeInner::Array { size: ArraySize::Dynamic, .. } => {
+            Err(format!("Array \"{}\" in \"{}\" has no fixed size", name, parent))
+        }
+        TypeInner::Struct { ref members, span } => {
+            structure(module, ty, members, span).map(Variable::Struct)
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+fn scalar(kind: ScalarKind) -> Scalar {
+    match kind {
+        ScalarKind::Bool => Scalar::Bool,
+        ScalarKind::Sint => Scalar::Int,
+        ScalarKind::Uint => Scalar::UInt,
+        ScalarKind::Float => Scalar::Float,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn mat2_columns() {
+        let module = naga::front::wgsl::parse_str(
+            "struct Params { a: f32, m: mat2x2<f32>, b: f32, }
+            @group(0) @binding(0) var<uniform> u: Params;",
+        )
+        .unwrap();
+        let bindings = bindings(&module).unwrap();
+        let block = match &bindings[0].resource {
+            Resource::Uniform(block) => block,
+            _ => panic!("Expected a uniform block"),
+        };
+        assert!(matches!(block.vars[1].1, Variable::Mat2(_)));
+        assert_eq!(block.offsets, vec![0, 8, 24]);
+
+        let m = Variable::Mat2([[1.0, 2.0], [3.0, 4.0]]);
+        let block = Variable::Struct(Struct {
+            vars: vec![("a".to_string(), Variable::Float(0.0)), ("m".to_string(), m), ("b".to_string(), Variable::Float(5.0))],
+            ..block.clone()
+        });
+        let floats = block
+            .bytes(16)
+            .chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(floats, vec![0.0, 0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 0.0]);
+    }
+
+    #[test]
+    fn constants_in_uniform_buffer() {
+        let mut module = naga::front::wgsl::parse_str(
+            "struct Constants { resolution: vec2<f32>, time: f32, }
+            // Not the var<push_constant> declaration, which is spelled differently:
+            var< push_constant >
+                c: Constants;
+            @group(0) @binding(0) var<uniform> push_constant_uniform: Constants;
+            @fragment fn main() -> @location(0) vec4<f32> {
+                return vec4<f32>(c.time + push_constant_uniform.time);
+            }",
+        )
+        .unwrap();
+        uniform_constants(&mut module);
+        naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+            .validate(&module)
+            .unwrap();
+        assert!(matches!(push_constants(&module), Ok(None)));
+
+        let mut bindings = bindings(&module).unwrap();
+        let constants = crate::program::reflect::take_constants(&mut bindings).unwrap();
+        assert_eq!(constants.offsets, vec![0, 8]);
+        assert_eq!(bindings.len(), 1);
+        assert_eq!((bindings[0].set, bindings[0].binding), (0, 0));
+        assert_eq!(bindings[0].name, "push_constant_uniform");
+    }
+}
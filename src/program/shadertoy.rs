@@ -0,0 +1,125 @@
+// Support for pasting in shaders from ShaderToy, which define a `mainImage`
+// function rather than `main` and expect a set of `i*` uniforms. These are
+// provided by a generated prelude mapping them onto the built-in constants.
+
+// Textures for `iChannel0` through `iChannel3` are bound in set 0, with their
+// samplers bound after them.
+const PRELUDE: &str = r#"#version 450
+
+layout(location = 0) in vec2 shadertoy_uv;
+layout(location = 0) out vec4 shadertoy_color;
+
+layout(push_constant) uniform Constants {
+    float t;
+    vec2 res;
+    float aspect;
+    vec2 mpos;
+    vec2 mclick;
+    float dt;
+    int frame;
+    vec4 date;
+} shadertoy_c;
+
+layout(set = 0, binding = 0) uniform texture2D iChannel0;
+layout(set = 0, binding = 1) uniform texture2D iChannel1;
+layout(set = 0, binding = 2) uniform texture2D iChannel2;
+layout(set = 0, binding = 3) uniform texture2D iChannel3;
+layout(set = 0, binding = 4) uniform sampler iChannel0Sampler;
+layout(set = 0, binding = 5) uniform sampler iChannel1Sampler;
+layout(set = 0, binding = 6) uniform sampler iChannel2Sampler;
+layout(set = 0, binding = 7) uniform sampler iChannel3Sampler;
+
+// The mouse position is in UV coordinates, so undo the transform applied to
+// it to get back to pixels. There's no way to tell whether the button is held,
+// so the click position always comes out positive.
+vec4 shadertoy_mouse() {
+    vec2 p = shadertoy_c.mclick;
+    if (shadertoy_c.aspect > 1.0)
+        p.x /= shadertoy_c.aspect;
+    else
+        p.y *= shadertoy_c.aspect;
+    p = (p + 0.5) * shadertoy_c.res;
+    return vec4(p, p);
+}
+
+vec3 iChannelResolution[4];
+
+#define iResolution vec3(shadertoy_c.res, 1.0)
+#define iTime shadertoy_c.t
+#define iTimeDelta shadertoy_c.dt
+#define iFrame shadertoy_c.frame
+#define iDate shadertoy_c.date
+#define iMouse shadertoy_mouse()
+
+// Combine each texture with its sampler, since wgpu doesn't support sampler2D bindings.
+#define iChannel0 sampler2D(iChannel0, iChannel0Sampler)
+#define iChannel1 sampler2D(iChannel1, iChannel1Sampler)
+#define iChannel2 sampler2D(iChannel2, iChannel2Sampler)
+#define iChannel3 sampler2D(iChannel3, iChannel3Sampler)
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord);
+
+void main() {
+    iChannelResolution[0] = vec3(textureSize(iChannel0, 0), 1.0);
+    iChannelResolution[1] = vec3(textureSize(iChannel1, 0), 1.0);
+    iChannelResolution[2] = vec3(textureSize(iChannel2, 0), 1.0);
+    iChannelResolution[3] = vec3(textureSize(iChannel3, 0), 1.0);
+
+    // ShaderToy puts the origin in the bottom left corner.
+    vec2 fragCoord = vec2(gl_FragCoord.x, iResolution.y - gl_FragCoord.y);
+    mainImage(shadertoy_color, fragCoord);
+    shadertoy_color.a = 1.0;
+}
+
+#line 1
+"#;
+
+// Check whether a shader is written for ShaderToy, by defining `mainImage` but not `main`.
+pub fn detect(source: &str) -> bool {
+    let source = strip_comments(source);
+    defines(&source, "mainImage") && !defines(&source, "main")
+}
+
+// Prepend the prelude to a ShaderToy shader. Line numbers in errors still
+// match up with the original source.
+pub fn wrap(source: &str) -> String {
+    format!("{}{}", PRELUDE, source)
+}
+
+// Check whether `name` appears as a whole identifier followed by an opening
+// parenthesis, ex. `void main()`.
+fn defines(source: &str, name: &str) -> bool {
+    let ident = |c: char| c.is_alphanumeric() || c == '_';
+    source.match_indices(name).any(|(i, _)| {
+        let before = source[..i].chars().next_back();
+        let after = source[i + name.len()..].trim_start();
+        !before.is_some_and(ident) && after.starts_with('(')
+    })
+}
+
+// Replace comments with spaces, so that commented out code isn't detected.
+fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(i) = rest.find('/') {
+        out.push_str(&rest[..i]);
+        let comment = &rest[i..];
+        let end = if comment.starts_with("//") {
+            comment.find('\n').unwrap_or(comment.len())
+        } else if comment.starts_with("/*") {
+            comment.find("*/").map_or(comment.len(), |j| j + 2)
+        } else {
+            1
+        };
+
+        if end == 1 {
+            out.push('/');
+        } else {
+            // Keep newlines so block comments don't join lines together.
+            out.extend(comment[..end].chars().map(|c| if c == '\n' { '\n' } else { ' ' }));
+        }
+        rest = &comment[end..];
+    }
+    out.push_str(rest);
+    out
+}
@@ -0,0 +1,78 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use crate::program::Diagnostic;
+
+// What the compiler thread is doing, or what came of the last compile.
+#[derive(Debug, Clone)]
+pub enum State {
+    Compiling,
+    Ok,
+    Failed(Vec<Diagnostic>),
+}
+
+// The state of the running program, shared between the compiler thread and
+// the renderer.
+pub struct Status {
+    // The shader file being compiled.
+    pub file: PathBuf,
+    pub state: State,
+    // How long the last compile took.
+    pub compile_time: Option<Duration>,
+    // Hash of the sources that produced the running program, if there is one.
+    pub hash: Option<u64>,
+    // When the running program stopped matching the files on disk, ie. when
+    // the first compile after it failed.
+    pub stale_since: Option<Instant>,
+    // Channels to send the state to after every compile.
+    listeners: Vec<Sender<State>>,
+}
+
+impl Status {
+    pub fn new(file: &Path) -> Self {
+        Self {
+            file: file.to_owned(),
+            state: State::Compiling,
+            compile_time: None,
+            hash: None,
+            stale_since: None,
+            listeners: vec![],
+        }
+    }
+
+    // Get the state after every compile from now on.
+    pub fn subscribe(&mut self) -> Receiver<State> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.listeners.push(tx);
+        rx
+    }
+
+    // Record a successful compile of sources with the given hash.
+    pub fn succeeded(&mut self, hash: u64, time: Duration) {
+        self.state = State::Ok;
+        self.compile_time = Some(time);
+        self.hash = Some(hash);
+        self.stale_since = None;
+        self.notify();
+    }
+
+    // Record a failed compile, which leaves the running program (if any)
+    // behind the files on disk.
+    pub fn failed(&mut self, diagnostics: Vec<Diagnostic>, time: Duration) {
+        self.state = State::Failed(diagnostics);
+        self.compile_time = Some(time);
+        if self.hash.is_some() && self.stale_since.is_none() {
+            self.stale_since = Some(Instant::now());
+        }
+        self.notify();
+    }
+
+    // Send the state to every listener, forgetting ones that went away.
+    fn notify(&mut self) {
+        let state = &self.state;
+        self.listeners.retain(|tx| tx.send(state.clone()).is_ok());
+    }
+}
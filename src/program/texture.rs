@@ -0,0 +1,223 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// A 2D texture bound to the shader, optionally loaded from an image file.
+#[derive(Debug, Clone)]
+pub struct Texture {
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub size: [u32; 2],
+    pub view: Arc<wgpu::TextureView>,
+}
+
+impl Texture {
+    // Create a 1x1 white texture to use until an image is loaded.
+    pub fn placeholder(device: &wgpu::Device, queue: &wgpu::Queue, name: &str) -> Self {
+        Self::create(
+            device,
+            queue,
+            name,
+            None,
+            [1, 1],
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            &[255; 4],
+        )
+    }
+
+    // Load a texture from an image file. HDR images are kept in a float format,
+    // everything else is treated as 8 bit sRGB.
+    pub fn load(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+        path: &Path,
+    ) -> Result<Self, String> {
+        // Keep the canonical path so changes to the file can be matched up later.
+        let path = &std::fs::canonicalize(path)
+            .map_err(|e| format!("Failed to load image \"{}\": {}", path.display(), e))?;
+
+        let image = image::open(path)
+            .map_err(|e| format!("Failed to load image \"{}\": {}", path.display(), e))?;
+        let size = [image.width(), image.height()];
+
+        // Creating a texture bigger than the device supports is a validation
+        // error, which would take down the renderer.
+        let max = device.limits().max_texture_dimension_2d;
+        if size[0] > max || size[1] > max {
+            return Err(format!(
+                "Image \"{}\" is {}x{}, larger than the {}x{} supported by the GPU",
+                path.display(),
+                size[0],
+                size[1],
+                max,
+                max
+            ));
+        }
+
+        let (format, data) = match image {
+            image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_) => {
+                let data = image
+                    .into_rgba32f()
+                    .into_raw()
+                    .into_iter()
+                    .flat_map(|f| half::f16::from_f32(f).to_le_bytes())
+                    .collect();
+                (wgpu::TextureFormat::Rgba16Float, data)
+            }
+            _ => (wgpu::TextureFormat::Rgba8UnormSrgb, image.into_rgba8().into_raw()),
+        };
+
+        Ok(Self::create(device, queue, name, Some(path.to_owned()), size, format, &data))
+    }
+
+    // Create an empty texture that can be rendered into and then sampled.
+    pub fn target(
+        device: &wgpu::Device,
+        name: &str,
+        size: [u32; 2],
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&Default::default());
+        Self {
+            name: name.to_string(),
+            path: None,
+            size,
+            view: Arc::new(view),
+        }
+    }
+
+    // Load the image again from the same path, ex. when it has changed on disk.
+    pub fn reload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<(), String> {
+        if let Some(path) = &self.path {
+            *self = Self::load(device, queue, &self.name, path)?;
+        }
+        Ok(())
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+        path: Option<PathBuf>,
+        size: [u32; 2],
+        format: wgpu::TextureFormat,
+        data: &[u8],
+    ) -> Self {
+        let extent = wgpu::Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(data.len() as u32 / size[1]),
+                rows_per_image: Some(size[1]),
+            },
+            extent,
+        );
+
+        let view = texture.create_view(&Default::default());
+        Self {
+            name: name.to_string(),
+            path,
+            size,
+            view: Arc::new(view),
+        }
+    }
+}
+
+// A sampler bound to the shader, with filter and wrap modes that can be changed at runtime.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    pub name: String,
+    pub filter: wgpu::FilterMode,
+    pub wrap: wgpu::AddressMode,
+    pub sampler: Arc<wgpu::Sampler>,
+}
+
+impl Sampler {
+    pub fn new(
+        device: &wgpu::Device,
+        name: &str,
+        filter: wgpu::FilterMode,
+        wrap: wgpu::AddressMode,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(name),
+            address_mode_u: wrap,
+            address_mode_v: wrap,
+            address_mode_w: wrap,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        });
+
+        Self {
+            name: name.to_string(),
+            filter,
+            wrap,
+            sampler: Arc::new(sampler),
+        }
+    }
+
+    // Recreate the sampler after changing the filter or wrap mode.
+    pub fn update(&mut self, device: &wgpu::Device) {
+        *self = Self::new(device, &self.name, self.filter, self.wrap);
+    }
+}
+
+// Parse a filter mode from an annotation argument.
+pub fn filter_mode(s: &str) -> Option<wgpu::FilterMode> {
+    match s {
+        "nearest" => Some(wgpu::FilterMode::Nearest),
+        "linear" => Some(wgpu::FilterMode::Linear),
+        _ => None,
+    }
+}
+
+// Parse a wrap mode from an annotation argument.
+pub fn wrap_mode(s: &str) -> Option<wgpu::AddressMode> {
+    match s {
+        "repeat" => Some(wgpu::AddressMode::Repeat),
+        "mirror" => Some(wgpu::AddressMode::MirrorRepeat),
+        "clamp" => Some(wgpu::AddressMode::ClampToEdge),
+        _ => None,
+    }
+}
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Map, Value};
+
+use crate::program::{preset, Program, Variable};
+
+// How to get from one keyframe to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    Smoothstep,
+    // A cubic bezier curve from (0, 0) to (1, 1) through the control points
+    // (x1, y1) and (x2, y2), like CSS's `cubic-bezier`.
+    Bezier([f32; 4]),
+}
+
+impl Easing {
+    // Map the progress between two keyframes to how far to blend between them.
+    pub fn ease(&self, u: f32) -> f32 {
+        let u = u.clamp(0.0, 1.0);
+        match *self {
+            Easing::Linear => u,
+            Easing::Smoothstep => u * u * (3.0 - 2.0 * u),
+            Easing::Bezier([x1, y1, x2, y2]) => {
+                // Keep the curve a function of x, then find the point on it at
+                // x = u by bisection since x always increases along it.
+                let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+                let (mut lo, mut hi) = (0.0, 1.0);
+                for _ in 0..24 {
+                    let s = (lo + hi) / 2.0;
+                    if bezier(x1, x2, s) < u {
+                        lo = s;
+                    } else {
+                        hi = s;
+                    }
+                }
+                bezier(y1, y2, (lo + hi) / 2.0)
+            }
+        }
+    }
+
+    fn to_json(self) -> Value {
+        match self {
+            Easing::Linear => json!("linear"),
+            Easing::Smoothstep => json!("smoothstep"),
+            Easing::Bezier(p) => json!(p),
+        }
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) if s == "linear" => Some(Easing::Linear),
+            Value::String(s) if s == "smoothstep" => Some(Easing::Smoothstep),
+            Value::Array(p) if p.len() == 4 => {
+                let mut points = [0.0; 4];
+                for (d, s) in points.iter_mut().zip(p) {
+                    *d = s.as_f64()? as f32;
+                }
+                Some(Easing::Bezier(points))
+            }
+            _ => None,
+        }
+    }
+}
+
+// One coordinate of a cubic bezier curve from 0 to 1 with the given control points.
+fn bezier(p1: f32, p2: f32, s: f32) -> f32 {
+    let r = 1.0 - s;
+    3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+}
+
+// A value of a variable at a point in time. Values are kept as JSON, like in
+// presets, and converted to the variable's type when they're used.
+#[derive(Debug, Clone)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: Value,
+    // Easing from this keyframe to the next.
+    pub easing: Easing,
+}
+
+// The keyframes of a single top level variable in a uniform block, sorted by time.
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub block: String,
+    pub var: String,
+    pub keys: Vec<Keyframe>,
+}
+
+impl Track {
+    // Set a variable to its value at time `t`, holding the first and last
+    // keyframes before and after them.
+    fn sample(&self, var: &mut Variable, t: f32) {
+        let next = self.keys.iter().position(|k| k.time > t).unwrap_or(self.keys.len());
+        let (a, b) = match (next.checked_sub(1).map(|i| &self.keys[i]), self.keys.get(next)) {
+            (Some(a), Some(b)) => (a, b),
+            (Some(k), None) | (None, Some(k)) => (k, k),
+            (None, None) => return,
+        };
+
+        let mut from = var.clone();
+        preset::assign_json(&mut from, &a.value);
+        if b.time > a.time {
+            let mut to = var.clone();
+            preset::assign_json(&mut to, &b.value);
+            from.mix(&to, a.easing.ease((t - a.time) / (b.time - a.time)) as f64);
+        }
+        *var = from;
+    }
+}
+
+// Keyframes animating uniform variables over the shader's time, kept in a
+// file next to the shader named after it, ex. `demo.timeline.json` for
+// `demo.glsl`. Tracks are keyed by block like in presets, then by variable:
+//
+// {
+//     "range": [0, 10],
+//     "loop": true,
+//     "tracks": {
+//         "Params": {
+//             "speed": [
+//                 { "time": 0, "value": 1, "easing": "smoothstep" },
+//                 { "time": 4, "value": 3, "easing": [0.42, 0, 0.58, 1] }
+//             ]
+//         }
+//     }
+// }
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    file: PathBuf,
+    pub tracks: Vec<Track>,
+    // Range of time to play, and whether to loop back to its start at the end.
+    pub range: [f32; 2],
+    pub looping: bool,
+}
+
+impl Timeline {
+    // An empty timeline for a shader file, which is saved to its timeline file.
+    pub fn empty(shader: &Path) -> Self {
+        Self {
+            file: shader.with_extension("timeline.json"),
+            tracks: vec![],
+            range: [0.0, 10.0],
+            looping: false,
+        }
+    }
+
+    // Load the timeline for a shader file, or an empty one if there's no
+    // timeline file yet.
+    pub fn load(shader: &Path) -> Result<Self, String> {
+        let mut timeline = Self::empty(shader);
+        let file = timeline.file.clone();
+        let err = |e: String| format!("Failed to load timeline \"{}\": {}", file.display(), e);
+
+        let content = match std::fs::read_to_string(&timeline.file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(timeline),
+            Err(e) => return Err(err(e.to_string())),
+        };
+        let root: Value = serde_json::from_str(&content).map_err(|e| err(e.to_string()))?;
+
+        if let Some([start, end]) = root["range"].as_array().map(Vec::as_slice) {
+            let start = start.as_f64().unwrap_or(0.0) as f32;
+            timeline.range = [start, end.as_f64().map_or(start, |e| e as f32)];
+        }
+        timeline.looping = root["loop"].as_bool().unwrap_or(false);
+
+        let blocks = root["tracks"].as_object().cloned().unwrap_or_default();
+        for (block, vars) in blocks {
+            for (var, keys) in vars.as_object().cloned().unwrap_or_default() {
+                let mut keys = keys
+                    .as_array()
+                    .ok_or_else(|| err(format!("expected a list of keyframes for {}.{}", block, var)))?
+                    .iter()
+                    .map(|k| {
+                        Some(Keyframe {
+                            time: k["time"].as_f64()? as f32,
+                            value: k.get("value")?.clone(),
+                            easing: Easing::from_json(&k["easing"]).unwrap_or(Easing::Linear),
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| err(format!("invalid keyframe for {}.{}", block, var)))?;
+                keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+                timeline.tracks.push(Track {
+                    block: block.clone(),
+                    var,
+                    keys,
+                });
+            }
+        }
+
+        Ok(timeline)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let mut blocks = Map::new();
+        for track in &self.tracks {
+            let keys = track
+                .keys
+                .iter()
+                .map(|k| json!({ "time": k.time, "value": k.value, "easing": k.easing.to_json() }))
+                .collect();
+            let vars = blocks.entry(track.block.clone()).or_insert_with(|| json!({}));
+            vars[&track.var] = Value::Array(keys);
+        }
+
+        let root = json!({ "range": self.range, "loop": self.looping, "tracks": blocks });
+        let content = serde_json::to_string_pretty(&root).unwrap();
+        std::fs::write(&self.file, content)
+            .map_err(|e| format!("Failed to save timeline \"{}\": {}", self.file.display(), e))
+    }
+
+    // Set every animated variable in a program to its value at time `t`.
+    pub fn apply(&self, program: &mut Program, t: f32) {
+        for (pass, group) in program.groups_mut() {
+            for uniform in group.uniforms.values_mut() {
+                let block = preset::key(pass, &uniform.name);
+                for track in self.tracks.iter().filter(|track| track.block == block) {
+                    if let Some((_, var)) = uniform.vars.iter_mut().find(|(name, _)| *name == track.var) {
+                        track.sample(var, t);
+                    }
+                }
+            }
+        }
+    }
+
+    // Add a keyframe holding the value of a variable at time `t`, replacing
+    // any keyframe already at that time.
+    pub fn insert(&mut self, block: &str, var: &str, value: &Variable, t: f32) {
+        let track = match self.tracks.iter().position(|track| track.block == block && track.var == var) {
+            Some(i) => &mut self.tracks[i],
+            None => {
+                self.tracks.push(Track {
+                    block: block.to_string(),
+                    var: var.to_string(),
+                    keys: vec![],
+                });
+                self.tracks.last_mut().unwrap()
+            }
+        };
+
+        let key = Keyframe {
+            time: t,
+            value: preset::to_json(value),
+            easing: Easing::Linear,
+        };
+        match track.keys.iter().position(|k| k.time >= t) {
+            Some(i) if track.keys[i].time == t => track.keys[i].value = key.value,
+            Some(i) => track.keys.insert(i, key),
+            None => track.keys.push(key),
+        }
+    }
+}
@@ -1,15 +1,117 @@
 use std::collections::HashMap;
 
+use crate::program::{Meta, Sampler, Texture};
+
+#[derive(Debug, Clone, Copy)]
 pub struct Constants {
     pub t: f32,
     pub resolution: [f32; 2],
     pub aspect: f32,
     pub mpos: [f32; 2],
     pub mclick: [f32; 2],
+    // Seconds since the last frame and the number of frames rendered so far.
+    pub dt: f32,
+    pub frame: u32,
+    // Year, month, day and seconds since midnight, see `render::clock::date`.
+    pub date: [f32; 4],
 }
 
 impl Constants {
-    pub const SIZE: u32 = 40;
+    pub const SIZE: u32 = 64;
+    // The set holding the constants in a uniform buffer at binding 0, for
+    // devices without push constants. Shaders can't use it for anything else
+    // on those devices.
+    pub const SET: u32 = 3;
+
+    // Whether the constants can be sent to shaders on a device as push
+    // constants, rather than through a uniform buffer.
+    pub fn push(device: &wgpu::Device) -> bool {
+        device.features().contains(wgpu::Features::PUSH_CONSTANTS)
+    }
+
+    // The constants laid out like shaders declare them, see `reflect::CONSTANTS`.
+    pub fn bytes(&self) -> Vec<u8> {
+        [
+            // Time
+            self.t.to_le_bytes(),
+            // Resolution, padded to 8 byte alignment
+            [0, 0, 0, 0],
+            self.resolution[0].to_le_bytes(),
+            self.resolution[1].to_le_bytes(),
+            // Aspect Ratio
+            self.aspect.to_le_bytes(),
+            // Mouse Position, padded to 8 byte alignment
+            [0, 0, 0, 0],
+            self.mpos[0].to_le_bytes(),
+            self.mpos[1].to_le_bytes(),
+            // Click Position
+            self.mclick[0].to_le_bytes(),
+            self.mclick[1].to_le_bytes(),
+            // Time Delta
+            self.dt.to_le_bytes(),
+            // Frame Number
+            self.frame.to_le_bytes(),
+            // Date
+            self.date[0].to_le_bytes(),
+            self.date[1].to_le_bytes(),
+            self.date[2].to_le_bytes(),
+            self.date[3].to_le_bytes(),
+        ]
+        .concat()
+    }
+}
+
+// A uniform buffer holding the constants, bound at `Constants::SET` in place
+// of push constants on devices that don't support them.
+pub struct ConstantsBuffer {
+    pub buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub layout_entries: Vec<wgpu::BindGroupLayoutEntry>,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ConstantsBuffer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("constants"),
+            size: Constants::SIZE as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout_entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                min_binding_size: None,
+                has_dynamic_offset: false,
+            },
+            count: None,
+        }];
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("constants"),
+            entries: &layout_entries,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("constants"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            buffer,
+            bind_group_layout,
+            layout_entries,
+            bind_group,
+        }
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue, consts: &Constants) {
+        queue.write_buffer(&self.buffer, 0, &consts.bytes());
+    }
 }
 
 impl Default for Constants {
@@ -20,82 +122,482 @@ impl Default for Constants {
             aspect: 1.0,
             mpos: [0.0; 2],
             mclick: [0.0; 2],
+            dt: 0.0,
+            frame: 0,
+            date: [0.0; 4],
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Variable {
+    Bool(bool),
     Int(i32),
+    UInt(u32),
     Float(f32),
     Vec2([f32; 2]),
     Vec3([f32; 3]),
     Vec4([f32; 4]),
+    IVec2([i32; 2]),
+    IVec3([i32; 3]),
+    IVec4([i32; 4]),
+    UVec2([u32; 2]),
+    UVec3([u32; 3]),
+    UVec4([u32; 4]),
+    // Matrices are stored column-major, matching GLSL.
+    Mat2([[f32; 2]; 2]),
+    Mat3([[f32; 3]; 3]),
+    Mat4([[f32; 4]; 4]),
+    Array(Array),
+    Struct(Struct),
+}
+
+#[derive(Debug, Clone)]
+pub struct Array {
+    // Distance in bytes between the start of consecutive elements.
+    pub stride: u32,
+    pub elems: Vec<Variable>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub name: String,
+    pub vars: Vec<(String, Variable)>,
+    // Byte offset of each variable in `vars` relative to the start of the struct.
+    pub offsets: Vec<u32>,
+    pub size: u32,
+    // Distance in bytes between the columns of a mat2 in the struct, which
+    // std140 pads out to a vec4 but WGSL doesn't.
+    pub mat2_stride: u32,
 }
 
 impl Variable {
+    // Size in bytes of the variable's data under std140 rules, not including
+    // any trailing padding. A mat2 in a WGSL struct is only 16 bytes, so
+    // sizes there come from naga's layout instead.
     pub fn size(&self) -> u32 {
         match self {
-            Variable::Int(_) => 4,
-            Variable::Float(_) => 4,
-            Variable::Vec2(_) => 8,
-            Variable::Vec3(_) => 16,
-            Variable::Vec4(_) => 16,
+            Variable::Bool(_) | Variable::Int(_) | Variable::UInt(_) | Variable::Float(_) => 4,
+            Variable::Vec2(_) | Variable::IVec2(_) | Variable::UVec2(_) => 8,
+            Variable::Vec3(_) | Variable::IVec3(_) | Variable::UVec3(_) => 12,
+            Variable::Vec4(_) | Variable::IVec4(_) | Variable::UVec4(_) => 16,
+            // Each column of a matrix is padded out to the size of a vec4.
+            Variable::Mat2(_) => 2 * 16,
+            Variable::Mat3(_) => 3 * 16,
+            Variable::Mat4(_) => 4 * 16,
+            Variable::Array(a) => a.stride * a.elems.len() as u32,
+            Variable::Struct(s) => s.size,
         }
     }
 
-    pub fn bytes(&self) -> Vec<u8> {
+    // Base alignment of the variable under std140 rules.
+    pub fn align(&self) -> u32 {
         match self {
+            Variable::Bool(_) | Variable::Int(_) | Variable::UInt(_) | Variable::Float(_) => 4,
+            Variable::Vec2(_) | Variable::IVec2(_) | Variable::UVec2(_) => 8,
+            _ => 16,
+        }
+    }
+
+    // The data of the variable as laid out in a uniform buffer, with the
+    // columns of a mat2 `mat2_stride` bytes apart.
+    pub fn bytes(&self, mat2_stride: u32) -> Vec<u8> {
+        match self {
+            // Booleans are 32 bits wide in uniform blocks.
+            Variable::Bool(b) => (*b as u32).to_le_bytes().to_vec(),
             Variable::Int(i) => i.to_le_bytes().to_vec(),
+            Variable::UInt(u) => u.to_le_bytes().to_vec(),
             Variable::Float(f) => f.to_le_bytes().to_vec(),
-            Variable::Vec2(v) => [v[0].to_le_bytes(), v[1].to_le_bytes()].concat().to_vec(),
-            Variable::Vec3(v) => [v[0].to_le_bytes(), v[1].to_le_bytes(), v[2].to_le_bytes()]
-                .concat()
-                .to_vec(),
-            Variable::Vec4(v) => [
-                v[0].to_le_bytes(),
-                v[1].to_le_bytes(),
-                v[2].to_le_bytes(),
-                v[3].to_le_bytes(),
-            ]
-            .concat()
-            .to_vec(),
+            Variable::Vec2(v) => v.iter().flat_map(|f| f.to_le_bytes()).collect(),
+            Variable::Vec3(v) => v.iter().flat_map(|f| f.to_le_bytes()).collect(),
+            Variable::Vec4(v) => v.iter().flat_map(|f| f.to_le_bytes()).collect(),
+            Variable::IVec2(v) => v.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            Variable::IVec3(v) => v.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            Variable::IVec4(v) => v.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            Variable::UVec2(v) => v.iter().flat_map(|u| u.to_le_bytes()).collect(),
+            Variable::UVec3(v) => v.iter().flat_map(|u| u.to_le_bytes()).collect(),
+            Variable::UVec4(v) => v.iter().flat_map(|u| u.to_le_bytes()).collect(),
+            Variable::Mat2(m) => m.iter().flat_map(|c| column_bytes(c, mat2_stride)).collect(),
+            Variable::Mat3(m) => m.iter().flat_map(|c| column_bytes(c, 16)).collect(),
+            Variable::Mat4(m) => m.iter().flat_map(|c| column_bytes(c, 16)).collect(),
+            Variable::Array(a) => {
+                let mut data = vec![0u8; self.size() as usize];
+                let offsets = (0..a.elems.len() as u32).map(|i| i * a.stride);
+                write_vars(&mut data, a.elems.iter().zip(offsets), mat2_stride);
+                data
+            }
+            Variable::Struct(s) => {
+                let mut data = vec![0u8; s.size as usize];
+                let vars = s.vars.iter().map(|(_, v)| v).zip(s.offsets.iter().copied());
+                write_vars(&mut data, vars, s.mat2_stride);
+                data
+            }
+        }
+    }
+
+    // The scalar components of a scalar or vector variable, or the columns of a matrix.
+    pub fn components(&self) -> Vec<Vec<f64>> {
+        fn cast<T: Copy + Into<f64>>(v: &[T]) -> Vec<f64> {
+            v.iter().map(|&x| x.into()).collect()
+        }
+
+        match self {
+            Variable::Bool(b) => vec![vec![*b as u32 as f64]],
+            Variable::Int(i) => vec![vec![*i as f64]],
+            Variable::UInt(u) => vec![vec![*u as f64]],
+            Variable::Float(f) => vec![vec![*f as f64]],
+            Variable::Vec2(v) => vec![cast(v)],
+            Variable::Vec3(v) => vec![cast(v)],
+            Variable::Vec4(v) => vec![cast(v)],
+            Variable::IVec2(v) => vec![cast(v)],
+            Variable::IVec3(v) => vec![cast(v)],
+            Variable::IVec4(v) => vec![cast(v)],
+            Variable::UVec2(v) => vec![cast(v)],
+            Variable::UVec3(v) => vec![cast(v)],
+            Variable::UVec4(v) => vec![cast(v)],
+            Variable::Mat2(m) => m.iter().map(|c| cast(c)).collect(),
+            Variable::Mat3(m) => m.iter().map(|c| cast(c)).collect(),
+            Variable::Mat4(m) => m.iter().map(|c| cast(c)).collect(),
+            Variable::Array(_) | Variable::Struct(_) => vec![],
+        }
+    }
+
+    // Overwrite the components of the variable, converting to its type as necessary.
+    // Missing components are left untouched.
+    pub fn set_components(&mut self, c: &[Vec<f64>]) {
+        fn copy<T>(dst: &mut [T], src: &[f64], f: impl Fn(f64) -> T) {
+            for (d, s) in dst.iter_mut().zip(src) {
+                *d = f(*s);
+            }
+        }
+        let int = |x: f64| x as i32;
+        let uint = |x: f64| x as u32;
+        let float = |x: f64| x as f32;
+
+        let col = match c.first() {
+            Some(col) => col.as_slice(),
+            None => return,
+        };
+        match self {
+            Variable::Bool(b) => copy(std::slice::from_mut(b), col, |x| x != 0.0),
+            Variable::Int(i) => copy(std::slice::from_mut(i), col, int),
+            Variable::UInt(u) => copy(std::slice::from_mut(u), col, uint),
+            Variable::Float(f) => copy(std::slice::from_mut(f), col, float),
+            Variable::Vec2(v) => copy(v, col, float),
+            Variable::Vec3(v) => copy(v, col, float),
+            Variable::Vec4(v) => copy(v, col, float),
+            Variable::IVec2(v) => copy(v, col, int),
+            Variable::IVec3(v) => copy(v, col, int),
+            Variable::IVec4(v) => copy(v, col, int),
+            Variable::UVec2(v) => copy(v, col, uint),
+            Variable::UVec3(v) => copy(v, col, uint),
+            Variable::UVec4(v) => copy(v, col, uint),
+            Variable::Mat2(m) => m.iter_mut().zip(c).for_each(|(d, s)| copy(d, s, float)),
+            Variable::Mat3(m) => m.iter_mut().zip(c).for_each(|(d, s)| copy(d, s, float)),
+            Variable::Mat4(m) => m.iter_mut().zip(c).for_each(|(d, s)| copy(d, s, float)),
+            Variable::Array(_) | Variable::Struct(_) => {}
+        }
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        matches!(
+            self,
+            Variable::Bool(_) | Variable::Int(_) | Variable::UInt(_) | Variable::Float(_)
+        )
+    }
+
+    fn is_composite(&self) -> bool {
+        matches!(self, Variable::Array(_) | Variable::Struct(_))
+    }
+
+    pub fn is_matrix(&self) -> bool {
+        matches!(self, Variable::Mat2(_) | Variable::Mat3(_) | Variable::Mat4(_))
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            Variable::Float(_)
+                | Variable::Vec2(_)
+                | Variable::Vec3(_)
+                | Variable::Vec4(_)
+                | Variable::Mat2(_)
+                | Variable::Mat3(_)
+                | Variable::Mat4(_)
+        )
+    }
+
+    // The GLSL name of a variable's type, ex. `vec3`, `float[4]` or the name of a struct.
+    pub fn type_name(&self) -> String {
+        let name = match self {
+            Variable::Bool(_) => "bool",
+            Variable::Int(_) => "int",
+            Variable::UInt(_) => "uint",
+            Variable::Float(_) => "float",
+            Variable::Vec2(_) => "vec2",
+            Variable::Vec3(_) => "vec3",
+            Variable::Vec4(_) => "vec4",
+            Variable::IVec2(_) => "ivec2",
+            Variable::IVec3(_) => "ivec3",
+            Variable::IVec4(_) => "ivec4",
+            Variable::UVec2(_) => "uvec2",
+            Variable::UVec3(_) => "uvec3",
+            Variable::UVec4(_) => "uvec4",
+            Variable::Mat2(_) => "mat2",
+            Variable::Mat3(_) => "mat3",
+            Variable::Mat4(_) => "mat4",
+            Variable::Array(a) => {
+                let elem = a.elems.first().map_or("?".to_string(), Variable::type_name);
+                return format!("{}[{}]", elem, a.elems.len());
+            }
+            Variable::Struct(s) => return s.name.clone(),
+        };
+        name.to_string()
+    }
+
+    // Copy the value of another variable into this one, trying to pick sane
+    // conversions if the type changed. Scalars convert between each other,
+    // vectors are truncated or padded with zeros, and matrices keep the
+    // overlapping upper-left block. Arrays copy over as many elements as they
+    // have in common, and structs match up members by name. Anything else is
+    // left alone.
+    pub fn assign(&mut self, other: &Variable) {
+        match (&mut *self, other) {
+            (Variable::Array(a), Variable::Array(oa)) => {
+                for (e, oe) in a.elems.iter_mut().zip(&oa.elems) {
+                    e.assign(oe);
+                }
+                return;
+            }
+            (Variable::Struct(s), Variable::Struct(os)) => {
+                assign_vars(&mut s.vars, &os.vars);
+                return;
+            }
+            _ => {}
+        }
+
+        if self.is_composite() || other.is_composite() {
+            return;
         }
+
+        let compatible = (self.is_matrix() && other.is_matrix())
+            || (self.is_scalar() && other.is_scalar())
+            || (!self.is_matrix() && !self.is_scalar() && !other.is_matrix() && !other.is_scalar());
+        if !compatible {
+            return;
+        }
+
+        let mut c = other.components();
+        if !self.is_matrix() {
+            // Pad vectors with zeros rather than leaving the old value in place.
+            c[0].resize(self.components()[0].len(), 0.0);
+        }
+        self.set_components(&c);
+    }
+
+    // Blend towards another variable of the same type, with `t` going from 0
+    // (this one) to 1 (the other). Floating point components are interpolated
+    // linearly, while integers and bools are rounded to the nearest value.
+    pub fn mix(&mut self, other: &Variable, t: f64) {
+        match (&mut *self, other) {
+            (Variable::Array(a), Variable::Array(oa)) => {
+                for (e, oe) in a.elems.iter_mut().zip(&oa.elems) {
+                    e.mix(oe, t);
+                }
+                return;
+            }
+            (Variable::Struct(s), Variable::Struct(os)) => {
+                for ((_, v), (_, ov)) in s.vars.iter_mut().zip(&os.vars) {
+                    v.mix(ov, t);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        if self.is_composite() || other.is_composite() {
+            return;
+        }
+
+        let float = self.is_float();
+        let c = self
+            .components()
+            .iter()
+            .zip(other.components())
+            .map(|(a, b)| {
+                a.iter()
+                    .zip(b)
+                    .map(|(x, y)| {
+                        let m = x + (y - x) * t;
+                        if float {
+                            m
+                        } else {
+                            m.round()
+                        }
+                    })
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        self.set_components(&c);
     }
 }
 
+// Copy values from variables in `other` into variables in `vars` with the same name.
+pub fn assign_vars(vars: &mut [(String, Variable)], other: &[(String, Variable)]) {
+    for (name, v) in vars {
+        if let Some((_, ov)) = other.iter().find(|(oname, _)| oname == name) {
+            v.assign(ov);
+        }
+    }
+}
+
+// Write the data of each variable into `data` at its offset.
+fn write_vars<'a>(data: &mut [u8], vars: impl Iterator<Item = (&'a Variable, u32)>, mat2_stride: u32) {
+    for (var, offset) in vars {
+        let bytes = var.bytes(mat2_stride);
+        let offset = offset as usize;
+        data[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+}
+
+// Pad a matrix column out to the stride between columns.
+fn column_bytes(c: &[f32], stride: u32) -> Vec<u8> {
+    let mut bytes: Vec<u8> = c.iter().flat_map(|f| f.to_le_bytes()).collect();
+    bytes.resize(stride as usize, 0);
+    bytes
+}
+
 pub struct UniformGroup {
     pub bind_group_layout: wgpu::BindGroupLayout,
+    // The entries the layout was created from, since wgpu can't describe a
+    // layout after the fact.
+    pub layout_entries: Vec<wgpu::BindGroupLayoutEntry>,
     pub bind_group: wgpu::BindGroup,
     pub uniforms: HashMap<u32, Uniform>,
+    pub textures: HashMap<u32, Texture>,
+    pub samplers: HashMap<u32, Sampler>,
+}
+
+impl UniformGroup {
+    pub fn new(
+        device: &wgpu::Device,
+        uniforms: HashMap<u32, Uniform>,
+        textures: HashMap<u32, Texture>,
+        samplers: HashMap<u32, Sampler>,
+    ) -> Self {
+        let uniform_entries = uniforms.keys().map(|j| wgpu::BindGroupLayoutEntry {
+            binding: *j,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                min_binding_size: None,
+                has_dynamic_offset: false,
+            },
+            count: None,
+        });
+        let texture_entries = textures.keys().map(|j| wgpu::BindGroupLayoutEntry {
+            binding: *j,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        });
+        let sampler_entries = samplers.keys().map(|j| wgpu::BindGroupLayoutEntry {
+            binding: *j,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        });
+
+        let layout_entries = uniform_entries
+            .chain(texture_entries)
+            .chain(sampler_entries)
+            .collect::<Vec<_>>();
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &layout_entries,
+        });
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &uniforms, &textures, &samplers);
+
+        Self {
+            bind_group_layout,
+            layout_entries,
+            bind_group,
+            uniforms,
+            textures,
+            samplers,
+        }
+    }
+
+    // Recreate the bind group after a texture or sampler has been replaced.
+    pub fn bind(&mut self, device: &wgpu::Device) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniforms,
+            &self.textures,
+            &self.samplers,
+        );
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniforms: &HashMap<u32, Uniform>,
+        textures: &HashMap<u32, Texture>,
+        samplers: &HashMap<u32, Sampler>,
+    ) -> wgpu::BindGroup {
+        let uniform_entries = uniforms.iter().map(|(j, u)| wgpu::BindGroupEntry {
+            binding: *j,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &u.buffer,
+                offset: 0,
+                size: None,
+            }),
+        });
+        let texture_entries = textures.iter().map(|(j, t)| wgpu::BindGroupEntry {
+            binding: *j,
+            resource: wgpu::BindingResource::TextureView(&t.view),
+        });
+        let sampler_entries = samplers.iter().map(|(j, s)| wgpu::BindGroupEntry {
+            binding: *j,
+            resource: wgpu::BindingResource::Sampler(&s.sampler),
+        });
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &uniform_entries
+                .chain(texture_entries)
+                .chain(sampler_entries)
+                .collect::<Vec<_>>(),
+        })
+    }
 }
 
 #[derive(Debug)]
 pub struct Uniform {
     pub name: String,
     pub vars: Vec<(String, Variable)>,
+    // Byte offset of each variable in `vars` within the buffer, as reflected from the shader.
+    pub offsets: Vec<u32>,
+    // How to show each variable in `vars` in the GUI.
+    pub meta: Vec<Meta>,
+    pub size: u32,
+    pub mat2_stride: u32,
     pub buffer: wgpu::Buffer,
 }
 
 impl Uniform {
     // Upload data in `vars` to the uniform buffer
     pub fn write(&self, queue: &wgpu::Queue) {
-        let mut data: Vec<u8> = vec![];
-        let mut offset = 0;
-        for (_, var) in &self.vars {
-            // Add padding until we're aligned to a multiple of the size
-            while offset % var.size() != 0 {
-                data.push(0);
-                offset += 1;
-            }
-
-            // Write each byte of the variable's data
-            for b in var.bytes() {
-                data.push(b);
-                offset += 1;
-            }
-        }
+        let mut data = vec![0u8; self.size as usize];
+        let vars = self.vars.iter().map(|(_, v)| v).zip(self.offsets.iter().copied());
+        write_vars(&mut data, vars, self.mat2_stride);
 
         queue.write_buffer(&self.buffer, 0, &data);
     }
-}
\ No newline at end of file
+}
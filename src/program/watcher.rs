@@ -1,27 +1,173 @@
-use std::{path::Path, sync::mpsc::Sender};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
 
+use crate::program::buffer;
 
-pub fn spawn(file: &Path, tx: Sender<()>) {
-    let file = file.to_owned();
+// How long to wait for things to settle after a change before recompiling.
+// Editors often touch several paths when saving, ex. writing a temporary file
+// and renaming it over the original.
+const DELAY: Duration = Duration::from_millis(100);
+
+// Watch the file and everything it depends on for changes, notifying the
+// compiler through `tx`. The file last compiled and the set of files the
+// compile read, including any includes, is sent back by the compiler through
+// `files_rx`, so switching to another file switches what gets watched too.
+pub fn spawn(file: &Path, tx: Sender<Option<PathBuf>>, files_rx: Receiver<(PathBuf, HashSet<PathBuf>)>) {
+    let mut file = file.to_owned();
+    let mut buffers = buffers(&file);
+    let mut root = file.parent().unwrap().to_owned();
     std::thread::spawn(move || {
         let (notify_tx, notify_rx) = std::sync::mpsc::channel();
 
-        let mut watcher = notify::raw_watcher(notify_tx).expect("Failed to initialize filesystem watcher!");
+        let mut watcher = notify::watcher(notify_tx, DELAY).expect("Failed to initialize filesystem watcher!");
 
+        // Folders are watched rather than the files themselves, so that files
+        // replaced by a rename (and so with a new inode) are still picked up.
         use notify::Watcher;
         watcher
-            .watch(file.parent().unwrap(), notify::RecursiveMode::Recursive)
+            .watch(&root, notify::RecursiveMode::Recursive)
             .unwrap();
 
+        let mut files = HashSet::new();
+        // Folders of includes from outside the main folder that are watched,
+        // and ones that stopped being watched because they were removed.
+        let mut dirs = HashSet::new();
+        let mut lost = HashSet::new();
         loop {
-            let ev = notify_rx.recv().unwrap();
-            if let (Some(path), Ok(op)) = (ev.path, ev.op) {
-                // Only notify the compiler on CLOSE_WRITE, since WRITE
-                // can happen before the full file is written.
-                if path == file && op == notify::op::CLOSE_WRITE {
-                    tx.send(()).unwrap();
+            // Pick up the files read by any compiles since the last check,
+            // moving over to the folder of a new main file.
+            for (main, new) in files_rx.try_iter() {
+                if main != file {
+                    let _ = watcher.unwatch(&root);
+                    root = main.parent().unwrap().to_owned();
+                    if let Err(e) = watcher.watch(&root, notify::RecursiveMode::Recursive) {
+                        log::warn!("Failed to watch \"{}\": {}", root.display(), e);
+                    }
+                    buffers = self::buffers(&main);
+                    file = main;
+                }
+                files = new;
+            }
+
+            // Watch the folders of includes from outside the main folder. If
+            // a removed folder is back, its contents may have changed while it
+            // wasn't watched, so recompile.
+            let wanted = files
+                .iter()
+                .filter_map(|f| f.parent())
+                .filter(|dir| !dir.starts_with(&root))
+                .map(Path::to_owned)
+                .collect::<HashSet<_>>();
+            for dir in dirs.difference(&wanted) {
+                let _ = watcher.unwatch(dir);
+            }
+            dirs.retain(|dir| wanted.contains(dir));
+            let mut changed = false;
+            for dir in &wanted {
+                if !dirs.contains(dir) && dir.is_dir() {
+                    if let Err(e) = watcher.watch(dir, notify::RecursiveMode::NonRecursive) {
+                        log::warn!("Failed to watch \"{}\": {}", dir.display(), e);
+                    }
+                    dirs.insert(dir.clone());
+                    changed |= lost.remove(dir);
+                }
+            }
+
+            // Wake up regularly to check for new files, since nothing else
+            // may happen on disk until an include outside the folder changes.
+            let mut next = match notify_rx.recv_timeout(Duration::from_millis(250)) {
+                Ok(ev) => Some(ev),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            while let Some(ev) = next {
+                match touched(ev) {
+                    Some(paths) => {
+                        for path in &paths {
+                            if dirs.remove(path) {
+                                lost.insert(path.clone());
+                            }
+                        }
+                        changed |= paths.iter().any(|p| buffers.contains(p) || files.contains(p));
+                    }
+                    // Events may have been missed, so assume anything changed.
+                    None => changed = true,
                 }
+
+                // Keep going until nothing else has happened for a bit, so
+                // that a burst of changes only recompiles once.
+                next = if changed { notify_rx.recv_timeout(DELAY).ok() } else { None };
+            }
+
+            if changed {
+                tx.send(None).unwrap();
             }
         }
     });
 }
+
+// The main file along with its buffer passes, which get recompiled along with it.
+fn buffers(file: &Path) -> HashSet<PathBuf> {
+    let mut buffers = buffer::files(file).into_iter().map(|(_, f)| f).collect::<HashSet<_>>();
+    buffers.insert(file.to_owned());
+    buffers
+}
+
+// Get the paths that an event created, wrote to, removed or renamed, or `None`
+// if the watcher may have missed events.
+fn touched(ev: notify::DebouncedEvent) -> Option<Vec<PathBuf>> {
+    use notify::DebouncedEvent::*;
+    match ev {
+        Create(path) | Write(path) | Remove(path) => Some(vec![path]),
+        Rename(from, to) => Some(vec![from, to]),
+        Rescan => None,
+        Error(e, path) => {
+            log::warn!("Filesystem watcher error: {}", e);
+            Some(path.into_iter().collect())
+        }
+        NoticeWrite(_) | NoticeRemove(_) | Chmod(_) => Some(vec![]),
+    }
+}
+
+// Watches the image files backing textures, so they can be reloaded when they
+// change on disk. Polled from the render thread rather than running its own.
+pub struct ImageWatcher {
+    watcher: notify::RecommendedWatcher,
+    rx: Receiver<notify::DebouncedEvent>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl ImageWatcher {
+    pub fn new() -> Self {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let watcher = notify::watcher(tx, DELAY).expect("Failed to initialize filesystem watcher!");
+
+        Self {
+            watcher,
+            rx,
+            dirs: HashSet::new(),
+        }
+    }
+
+    // Start watching an image file, if its folder isn't watched already.
+    pub fn watch(&mut self, file: &Path) {
+        if let Some(dir) = file.parent() {
+            if self.dirs.insert(dir.to_owned()) {
+                use notify::Watcher;
+                if let Err(e) = self.watcher.watch(dir, notify::RecursiveMode::NonRecursive) {
+                    log::warn!("Failed to watch \"{}\": {}", dir.display(), e);
+                }
+            }
+        }
+    }
+
+    // Get the files that have been written to or replaced since the last call.
+    pub fn changed(&self) -> HashSet<PathBuf> {
+        self.rx.try_iter().filter_map(touched).flatten().collect()
+    }
+}
@@ -0,0 +1,570 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Cursor, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    program::{preset, Program, State, Status},
+    remote::{self, websocket, Arg, Command},
+};
+
+// Largest request body accepted.
+const MAX_BODY: usize = 1 << 20;
+
+// Largest request line and headers accepted, together.
+const MAX_HEAD: u64 = 1 << 16;
+
+// How long to wait for a client to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// A local HTTP server for scripting the playground, which reads and writes the
+// running program directly and asks the renderer for anything else:
+//
+//     GET  /shader                   {"file": ...} of the shader file
+//     POST /shader                   Switch to the shader file in {"file": ...}
+//     GET  /status                   State of the last compile, with any errors
+//     GET  /uniforms                 Every uniform block with its variables,
+//                                    their types and values
+//     PUT  /uniforms/<block>/<var>   Set a variable to the value in the body,
+//                                    in the same form as presets
+//     GET  /screenshot               PNG of the last frame, without the GUI
+//     GET  /events                   WebSocket pushing {"event": "reloaded"}
+//                                    or {"event": "compile_failed"} along with
+//                                    the status after every compile
+//
+// Blocks in buffer passes are keyed like in presets, ex. `Buffer%20A/Params`.
+// Bodies must be sent as `application/json`. Requests from web pages other
+// than ones served from the same address, or through any host name other than
+// a loopback one, are refused so that other sites can't drive the playground.
+pub fn spawn(
+    port: u16,
+    program: Arc<Mutex<Option<Program>>>,
+    status: Arc<Mutex<Status>>,
+    tx: Sender<Command>,
+) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("Failed to listen for HTTP on port {}: {}", port, e))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Failed to accept HTTP connection: {}", e);
+                    continue;
+                }
+            };
+
+            // Serve every connection on its own thread, since WebSockets
+            // stay open.
+            let program = Arc::clone(&program);
+            let status = Arc::clone(&status);
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = serve(stream, port, &program, &status, &tx) {
+                    log::warn!("HTTP: {}", e);
+                }
+            });
+        }
+    });
+
+    log::info!("Listening for HTTP on http://127.0.0.1:{}", port);
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    // Header names are lowercase.
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    code: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(code: u16, value: Value) -> Self {
+        Self {
+            code,
+            content_type: "application/json",
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    fn error(code: u16, message: impl Into<String>) -> Self {
+        Self::json(code, json!({ "error": message.into() }))
+    }
+}
+
+// Handle a single request on a connection, which is closed afterwards unless
+// it turns into a WebSocket.
+fn serve(
+    stream: TcpStream,
+    port: u16,
+    program: &Mutex<Option<Program>>,
+    status: &Mutex<Status>,
+    tx: &Sender<Command>,
+) -> Result<(), String> {
+    stream.set_read_timeout(Some(READ_TIMEOUT)).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let request = read_request(&mut reader)?;
+
+    if let Err(response) = check_request(&request, port) {
+        return write_response(&stream, &response).map_err(|e| format!("Failed to write response: {}", e));
+    }
+
+    let path = decode_path(request.path.split('?').next().unwrap_or_default());
+    let response = match (request.method.as_str(), path.as_str()) {
+        ("GET", "/events") => return events(stream, reader, &request, status),
+        ("GET", "/shader") => Response::json(200, json!({ "file": status.lock().unwrap().file })),
+        ("POST", "/shader") => open(&request, tx),
+        ("GET", "/status") => {
+            let status = status.lock().unwrap();
+            Response::json(200, status_json(&status, &status.state))
+        }
+        ("GET", "/uniforms") => match program.lock().unwrap().as_ref() {
+            Some(program) => Response::json(200, uniforms_json(program)),
+            None => Response::error(503, "No program is running"),
+        },
+        ("PUT", _) if path.starts_with("/uniforms/") => set(&path["/uniforms/".len()..], &request, program),
+        ("GET", "/screenshot") => screenshot(tx),
+        (_, "/events" | "/shader" | "/status" | "/uniforms" | "/screenshot") => {
+            Response::error(405, "Method not allowed")
+        }
+        _ => Response::error(404, "Not found"),
+    };
+
+    write_response(&stream, &response).map_err(|e| format!("Failed to write response: {}", e))
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, String> {
+    let mut head = reader.take(MAX_HEAD);
+    let mut line = String::new();
+    read_line(&mut head, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(format!("Invalid request line \"{}\"", line.trim())),
+    };
+
+    let mut headers = HashMap::new();
+    loop {
+        read_line(&mut head, &mut line)?;
+        match line.trim_end().split_once(':') {
+            Some((name, value)) => headers.insert(name.trim().to_lowercase(), value.trim().to_string()),
+            None => break,
+        };
+    }
+
+    let len = match headers.get("content-length") {
+        Some(len) => len.parse().map_err(|_| format!("Invalid Content-Length \"{}\"", len))?,
+        None => 0,
+    };
+    if len > MAX_BODY {
+        return Err(format!("Request body of {} bytes is too large", len));
+    }
+    let mut body = vec![0; len];
+    head.into_inner().read_exact(&mut body).map_err(|e| e.to_string())?;
+
+    Ok(Request {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+// Read a line of the request head into `line`, failing if it's cut short by
+// the client or the size limit.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), String> {
+    line.clear();
+    reader.read_line(line).map_err(|e| e.to_string())?;
+    if !line.ends_with('\n') {
+        return Err("Request headers are incomplete or too large".to_string());
+    }
+    Ok(())
+}
+
+// Refuse requests that a web page could have sent on the user's behalf: ones
+// from another origin, ones through a host name that isn't a loopback one,
+// which could be a DNS rebinding attack, and bodies that aren't JSON, which
+// pages can send without a CORS preflight.
+fn check_request(request: &Request, port: u16) -> Result<(), Response> {
+    if let Some(origin) = request.headers.get("origin") {
+        let allowed = [format!("http://127.0.0.1:{}", port), format!("http://localhost:{}", port)];
+        if !allowed.contains(origin) {
+            return Err(Response::error(403, format!("Requests from \"{}\" aren't allowed", origin)));
+        }
+    }
+
+    let host = request.headers.get("host").map(String::as_str).unwrap_or_default();
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.parse::<u16>().is_ok() => name,
+        _ => host,
+    };
+    if !matches!(name, "127.0.0.1" | "localhost") {
+        return Err(Response::error(403, format!("Host \"{}\" isn't allowed", host)));
+    }
+
+    if matches!(request.method.as_str(), "POST" | "PUT") {
+        let content_type = request.headers.get("content-type").map(String::as_str).unwrap_or_default();
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        if !mime.eq_ignore_ascii_case("application/json") {
+            return Err(Response::error(415, "Expected a body of type application/json"));
+        }
+    }
+    Ok(())
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response) -> std::io::Result<()> {
+    let reason = match response.code {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        415 => "Unsupported Media Type",
+        500 => "Internal Server Error",
+        _ => "Service Unavailable",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.code,
+        reason,
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+// Decode percent-encoded characters in a path, ex. `%20` for a space.
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// The status of the program, given the state separately since events are sent
+// with the state they were sent with rather than the current one.
+fn status_json(status: &Status, state: &State) -> Value {
+    let (state, errors) = match state {
+        State::Compiling => ("compiling", &[][..]),
+        State::Ok => ("ok", &[][..]),
+        State::Failed(diagnostics) => ("failed", diagnostics.as_slice()),
+    };
+    let errors = errors
+        .iter()
+        .map(|d| json!({ "file": d.file, "line": d.line, "column": d.column, "message": d.message }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "file": status.file,
+        "state": state,
+        "errors": errors,
+        "compile_time": status.compile_time.map(|t| t.as_secs_f64()),
+        "hash": status.hash.map(|h| format!("{:016x}", h)),
+    })
+}
+
+fn uniforms_json(program: &Program) -> Value {
+    let mut blocks = vec![];
+    for (pass, group) in program.groups() {
+        let mut uniforms = group.uniforms.iter().collect::<Vec<_>>();
+        uniforms.sort_by_key(|(binding, _)| **binding);
+        for (binding, uniform) in uniforms {
+            let vars = uniform
+                .vars
+                .iter()
+                .map(|(name, var)| {
+                    json!({ "name": name, "type": var.type_name(), "value": preset::to_json(var) })
+                })
+                .collect::<Vec<_>>();
+            blocks.push(json!({
+                "block": preset::key(pass, &uniform.name),
+                "pass": pass,
+                "binding": binding,
+                "size": uniform.size,
+                "vars": vars,
+            }));
+        }
+    }
+    Value::Array(blocks)
+}
+
+fn body_json(request: &Request) -> Result<Value, Response> {
+    serde_json::from_slice(&request.body).map_err(|e| Response::error(400, format!("Invalid JSON: {}", e)))
+}
+
+fn open(request: &Request, tx: &Sender<Command>) -> Response {
+    let body = match body_json(request) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let file = match body["file"].as_str() {
+        Some(file) => PathBuf::from(file),
+        None => return Response::error(400, "Expected {\"file\": <path>}"),
+    };
+    match std::fs::canonicalize(&file) {
+        Ok(file) => match tx.send(Command::Open(file.clone())) {
+            Ok(()) => Response::json(200, json!({ "file": file })),
+            Err(_) => Response::error(503, "The renderer has stopped"),
+        },
+        Err(e) => Response::error(400, format!("Failed to open \"{}\": {}", file.display(), e)),
+    }
+}
+
+// Set the variable at `path`, ex. `Params/swirl`, to the JSON value in the body.
+fn set(path: &str, request: &Request, program: &Mutex<Option<Program>>) -> Response {
+    let (block, var) = match path.rsplit_once('/') {
+        Some(split) => split,
+        None => return Response::error(404, "Expected /uniforms/<block>/<variable>"),
+    };
+    let value = match body_json(request) {
+        Ok(value) => value,
+        Err(response) => return response,
+    };
+
+    let mut args = vec![];
+    flatten(&value, &mut args);
+    let mut program = program.lock().unwrap();
+    let program = match program.as_mut() {
+        Some(program) => program,
+        None => return Response::error(503, "No program is running"),
+    };
+    match remote::set(program, block, var, &args) {
+        Ok(()) => Response::json(200, json!({ "block": block, "var": var, "value": value })),
+        Err(e) => Response::error(400, e),
+    }
+}
+
+// Flatten a JSON value into the values of each component, ex. `[[1, 0], [0, 1]]`
+// into `1 0 0 1`.
+fn flatten(value: &Value, args: &mut Vec<Arg>) {
+    match value {
+        Value::Array(values) => values.iter().for_each(|v| flatten(v, args)),
+        Value::Bool(b) => args.push(Arg::Bool(*b)),
+        Value::Number(n) => args.push(match n.as_i64() {
+            Some(i) => Arg::Int(i),
+            None => Arg::Float(n.as_f64().unwrap_or_default()),
+        }),
+        Value::String(s) => args.push(Arg::String(s.clone())),
+        Value::Null | Value::Object(_) => args.push(Arg::String(value.to_string())),
+    }
+}
+
+fn screenshot(tx: &Sender<Command>) -> Response {
+    let (image_tx, image_rx) = std::sync::mpsc::channel();
+    if tx.send(Command::Screenshot(image_tx)).is_err() {
+        return Response::error(503, "The renderer has stopped");
+    }
+    let image = match image_rx.recv_timeout(Duration::from_secs(5)) {
+        Ok(Ok(image)) => image,
+        Ok(Err(e)) => return Response::error(500, e),
+        Err(_) => return Response::error(503, "Timed out waiting for a frame"),
+    };
+
+    let mut png = Cursor::new(vec![]);
+    match image.write_to(&mut png, image::ImageOutputFormat::Png) {
+        Ok(()) => Response {
+            code: 200,
+            content_type: "image/png",
+            body: png.into_inner(),
+        },
+        Err(e) => Response::error(500, format!("Failed to encode PNG: {}", e)),
+    }
+}
+
+// Upgrade the connection to a WebSocket and push the status after every
+// compile until the client goes away.
+fn events(
+    stream: TcpStream,
+    mut reader: BufReader<TcpStream>,
+    request: &Request,
+    status: &Mutex<Status>,
+) -> Result<(), String> {
+    let key = match request.headers.get("sec-websocket-key") {
+        Some(key) => key,
+        None => {
+            let response = Response::error(400, "Expected a WebSocket upgrade");
+            return write_response(&stream, &response).map_err(|e| e.to_string());
+        }
+    };
+    // The connection stays open for as long as the client wants events.
+    stream.set_read_timeout(None).map_err(|e| e.to_string())?;
+    let mut writer = stream;
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        websocket::accept_key(key)
+    )
+    .map_err(|e| e.to_string())?;
+
+    let rx = status.lock().unwrap().subscribe();
+    let writer = Arc::new(Mutex::new(writer));
+
+    // Answer pings and notice when the client closes the connection.
+    let closed = Arc::new(AtomicBool::new(false));
+    {
+        let writer = Arc::clone(&writer);
+        let closed = Arc::clone(&closed);
+        std::thread::spawn(move || {
+            while let Ok((opcode, payload)) = websocket::read_frame(&mut reader) {
+                let mut writer = writer.lock().unwrap();
+                match opcode {
+                    websocket::PING => {
+                        let _ = websocket::write_frame(&mut *writer, websocket::PONG, &payload);
+                    }
+                    websocket::CLOSE => {
+                        let _ = websocket::write_frame(&mut *writer, websocket::CLOSE, &payload);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            closed.store(true, Ordering::Relaxed);
+        });
+    }
+
+    while !closed.load(Ordering::Relaxed) {
+        let state = match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(state) => state,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        let mut event = status_json(&status.lock().unwrap(), &state);
+        event["event"] = json!(match state {
+            State::Failed(_) => "compile_failed",
+            _ => "reloaded",
+        });
+        let mut writer = writer.lock().unwrap();
+        if websocket::write_frame(&mut *writer, websocket::TEXT, event.to_string().as_bytes()).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, headers: &[(&str, &str)]) -> Request {
+        Request {
+            method: method.to_string(),
+            path: "/uniforms/Params/swirl".to_string(),
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body: vec![],
+        }
+    }
+
+    fn check(method: &str, headers: &[(&str, &str)]) -> Result<(), u16> {
+        check_request(&request(method, headers), 8080).map_err(|response| response.code)
+    }
+
+    #[test]
+    fn origin() {
+        assert_eq!(check("GET", &[("host", "127.0.0.1:8080")]), Ok(()));
+        assert_eq!(check("GET", &[("host", "localhost:8080"), ("origin", "http://localhost:8080")]), Ok(()));
+        assert_eq!(check("GET", &[("host", "127.0.0.1:8080"), ("origin", "http://127.0.0.1:8080")]), Ok(()));
+        assert_eq!(check("GET", &[("host", "127.0.0.1:8080"), ("origin", "http://127.0.0.1:8081")]), Err(403));
+        assert_eq!(check("GET", &[("host", "127.0.0.1:8080"), ("origin", "https://example.com")]), Err(403));
+        assert_eq!(check("GET", &[("host", "127.0.0.1:8080"), ("origin", "null")]), Err(403));
+    }
+
+    #[test]
+    fn host() {
+        assert_eq!(check("GET", &[("host", "localhost")]), Ok(()));
+        assert_eq!(check("GET", &[("host", "127.0.0.1")]), Ok(()));
+        assert_eq!(check("GET", &[]), Err(403));
+        assert_eq!(check("GET", &[("host", "example.com:8080")]), Err(403));
+        assert_eq!(check("GET", &[("host", "localhost.example.com")]), Err(403));
+        assert_eq!(check("GET", &[("host", "[::1]:8080")]), Err(403));
+        assert_eq!(check("GET", &[("host", "127.0.0.1:http")]), Err(403));
+    }
+
+    #[test]
+    fn content_type() {
+        let host = ("host", "127.0.0.1:8080");
+        assert_eq!(check("PUT", &[host, ("content-type", "application/json")]), Ok(()));
+        assert_eq!(check("POST", &[host, ("content-type", "Application/JSON; charset=utf-8")]), Ok(()));
+        assert_eq!(check("PUT", &[host]), Err(415));
+        assert_eq!(check("POST", &[host, ("content-type", "text/plain")]), Err(415));
+        assert_eq!(check("POST", &[host, ("content-type", "application/x-www-form-urlencoded")]), Err(415));
+        assert_eq!(check("GET", &[host, ("content-type", "text/plain")]), Ok(()));
+    }
+
+    #[test]
+    fn parse() {
+        let data = b"PUT /uniforms/Buffer%20A/Params/swirl?x=1 HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 3\r\n\r\n0.5extra";
+        let request = read_request(&mut &data[..]).unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/uniforms/Buffer%20A/Params/swirl?x=1");
+        assert_eq!(request.headers["host"], "localhost");
+        assert_eq!(request.headers["content-type"], "application/json");
+        assert_eq!(request.body, b"0.5");
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(read_request(&mut &b"GET\r\n\r\n"[..]).is_err());
+        assert!(read_request(&mut &b"GET / HTTP/1.1\r\nContent-Length: x\r\n\r\n"[..]).is_err());
+        assert!(read_request(&mut &b"GET / HTTP/1.1\r\nContent-Length: 4\r\n\r\nab"[..]).is_err());
+        let large = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY + 1);
+        assert!(read_request(&mut large.as_bytes()).is_err());
+
+        // Headers cut short by the client, or too long to ever end.
+        assert!(read_request(&mut &b"GET / HTTP/1.1\r\nHost: local"[..]).is_err());
+        let long = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD as usize));
+        assert!(read_request(&mut long.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn renderer_stopped() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        drop(rx);
+        assert_eq!(screenshot(&tx).code, 503);
+
+        let mut request = request("POST", &[]);
+        request.body = json!({ "file": "." }).to_string().into_bytes();
+        assert_eq!(open(&request, &tx).code, 503);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(decode_path("/uniforms/Buffer%20A/Params/x"), "/uniforms/Buffer A/Params/x");
+        assert_eq!(decode_path("/a%2fb"), "/a/b");
+        assert_eq!(decode_path("/100%"), "/100%");
+        assert_eq!(decode_path("/%zz%4"), "/%zz%4");
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::mpsc::Sender,
+};
+
+use crate::program::{preset, Program, Variable};
+
+pub mod http;
+pub mod osc;
+mod websocket;
+
+// Something for the renderer to do on behalf of a remote controller.
+#[derive(Debug)]
+pub enum Command {
+    // Set a top level variable in a uniform block, keyed like in presets.
+    Set {
+        block: String,
+        var: String,
+        args: Vec<Arg>,
+    },
+    // Load a preset by name, or from a path to a `.json` file.
+    Preset(String),
+    // Seek to a time in seconds.
+    Time(f32),
+    // Start sending the values of variables to an OSC client whenever they change.
+    Subscribe(SocketAddr),
+    // Switch to another shader file.
+    Open(PathBuf),
+    // Send back the next frame rendered, without the GUI.
+    Screenshot(Sender<Result<image::RgbaImage, String>>),
+}
+
+// A single value sent by or to a remote controller.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl Arg {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Arg::Int(i) => Some(*i as f64),
+            Arg::Float(f) => Some(*f),
+            Arg::Bool(b) => Some(*b as u32 as f64),
+            Arg::String(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::Int(i) => write!(f, "{}", i),
+            Arg::Float(x) => write!(f, "{}", x),
+            Arg::Bool(b) => write!(f, "{}", b),
+            Arg::String(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+// Set a top level variable in a uniform block from one value for each of its
+// components, with the columns of matrices one after the other. Any number is
+// accepted for floats, whole numbers for ints, non-negative whole numbers for
+// uints, and bools or 0 and 1 for bools. Arrays and structs can't be set.
+pub fn set(program: &mut Program, block: &str, var: &str, args: &[Arg]) -> Result<(), String> {
+    let target = program
+        .groups_mut()
+        .flat_map(|(pass, group)| group.uniforms.values_mut().map(move |u| (pass, u)))
+        .find(|(pass, u)| preset::key(*pass, &u.name) == block)
+        .ok_or_else(|| format!("No uniform block \"{}\"", block))?
+        .1
+        .vars
+        .iter_mut()
+        .find(|(name, _)| name == var)
+        .map(|(_, v)| v)
+        .ok_or_else(|| format!("No variable \"{}\" in \"{}\"", var, block))?;
+
+    let mut c = target.components();
+    let n = c.iter().map(Vec::len).sum::<usize>();
+    if n == 0 {
+        return Err(format!("{}.{} is an array or struct and can't be set", block, var));
+    }
+    if args.len() != n {
+        return Err(format!("{}.{} takes {} values, got {}", block, var, n, args.len()));
+    }
+
+    for (x, arg) in c.iter_mut().flatten().zip(args) {
+        *x = convert(target, arg).ok_or_else(|| format!("Invalid value {} for {}.{}", arg, block, var))?;
+    }
+    target.set_components(&c);
+    Ok(())
+}
+
+// Check a value against the type of a variable's components.
+fn convert(var: &Variable, arg: &Arg) -> Option<f64> {
+    let x = match arg {
+        Arg::Bool(b) => return matches!(var, Variable::Bool(_)).then_some(*b as u32 as f64),
+        Arg::String(_) => return None,
+        _ => arg.as_f64()?,
+    };
+    let whole = x.fract() == 0.0;
+    match var {
+        _ if var.is_float() => Some(x),
+        Variable::Bool(_) => (x == 0.0 || x == 1.0).then_some(x),
+        Variable::UInt(_) | Variable::UVec2(_) | Variable::UVec3(_) | Variable::UVec4(_) => {
+            (whole && x >= 0.0 && x <= u32::MAX as f64).then_some(x)
+        }
+        _ => (whole && x >= i32::MIN as f64 && x <= i32::MAX as f64).then_some(x),
+    }
+}
+
+// The values of every top level variable that can be set, as (block, variable,
+// one value for each component).
+pub fn values(program: &Program) -> Vec<(String, String, Vec<Arg>)> {
+    let mut values = vec![];
+    for (pass, group) in program.groups() {
+        for uniform in group.uniforms.values() {
+            for (name, var) in &uniform.vars {
+                let args = var
+                    .components()
+                    .into_iter()
+                    .flatten()
+                    .map(|x| match var {
+                        Variable::Bool(_) => Arg::Bool(x != 0.0),
+                        _ if var.is_float() => Arg::Float(x),
+                        _ => Arg::Int(x as i64),
+                    })
+                    .collect::<Vec<_>>();
+                if !args.is_empty() {
+                    values.push((preset::key(pass, &uniform.name), name.clone(), args));
+                }
+            }
+        }
+    }
+    values
+}
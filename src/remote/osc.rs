@@ -0,0 +1,374 @@
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::mpsc::Sender,
+};
+
+use crate::{
+    program::{preset, Program},
+    remote::{self, Arg, Command},
+};
+
+// Large enough for any UDP datagram.
+const MAX_PACKET: usize = 65536;
+
+// Controls the program over OSC (Open Sound Control) on a UDP port. Variables
+// are set by sending their values to `/<block>/<variable>`, or
+// `/<pass>/<block>/<variable>` for buffer passes, ex. `/Params/swirl 0.5` or
+// `/A/Params/tint 1 0.5 0`. Besides those:
+//
+//     /preset <name>   Load a preset
+//     /time <seconds>  Seek to a time
+//     /sync            Send the values of every variable back to the sender,
+//                      and again whenever they change
+pub struct Osc {
+    socket: UdpSocket,
+    // Clients to send values to, and the values last sent for each address.
+    targets: Vec<SocketAddr>,
+    sent: HashMap<String, Vec<Arg>>,
+}
+
+impl Osc {
+    // Listen for OSC messages on a port of the interface at `bind` on a separate
+    // thread, passing them on to the renderer through `tx`. Anyone who can reach
+    // the port can change variables, so this should be the loopback address
+    // unless asked otherwise. Values are sent to `send` if given, ex.
+    // `192.168.1.20:9001`, like for clients that send `/sync`.
+    pub fn spawn(bind: IpAddr, port: u16, send: Option<&str>, tx: Sender<Command>) -> Result<Self, String> {
+        let socket = UdpSocket::bind((bind, port))
+            .map_err(|e| format!("Failed to listen for OSC on {}:{}: {}", bind, port, e))?;
+        let targets = match send {
+            Some(addr) => addr
+                .to_socket_addrs()
+                .map_err(|e| format!("Invalid OSC address \"{}\": {}", addr, e))?
+                .take(1)
+                .collect(),
+            None => vec![],
+        };
+
+        let recv = socket
+            .try_clone()
+            .map_err(|e| format!("Failed to listen for OSC on port {}: {}", port, e))?;
+        std::thread::spawn(move || {
+            let mut buf = vec![0; MAX_PACKET];
+            loop {
+                // Errors are usually from a client going away after being
+                // sent to, so just carry on.
+                let (n, from) = match recv.recv_from(&mut buf) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        log::warn!("Failed to receive OSC: {}", e);
+                        continue;
+                    }
+                };
+
+                let mut messages = vec![];
+                if let Err(e) = decode(&buf[..n], &mut messages) {
+                    log::warn!("Invalid OSC packet from {}: {}", from, e);
+                    continue;
+                }
+                for (address, args) in messages {
+                    match command(&address, args, from) {
+                        Ok(command) => {
+                            if tx.send(command).is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => log::warn!("OSC {}: {}", address, e),
+                    }
+                }
+            }
+        });
+
+        log::info!("Listening for OSC on {}:{}", bind, port);
+        Ok(Self {
+            socket,
+            targets,
+            sent: HashMap::new(),
+        })
+    }
+
+    // Start sending values to a client, beginning with every one of them.
+    pub fn subscribe(&mut self, addr: SocketAddr) {
+        if !self.targets.contains(&addr) {
+            self.targets.push(addr);
+        }
+        self.sent.clear();
+    }
+
+    // Send the values of the variables that changed since the last time to
+    // every client.
+    pub fn send(&mut self, program: &Program) {
+        if self.targets.is_empty() {
+            return;
+        }
+
+        for (block, var, args) in remote::values(program) {
+            let address = format!("{}/{}", address(&block), var);
+            if self.sent.get(&address) == Some(&args) {
+                continue;
+            }
+
+            let packet = encode(&address, &args);
+            for target in &self.targets {
+                if let Err(e) = self.socket.send_to(&packet, target) {
+                    log::warn!("Failed to send OSC to {}: {}", target, e);
+                }
+            }
+            self.sent.insert(address, args);
+        }
+    }
+}
+
+// The address of a uniform block, ex. `/Params`, or `/A/Params` for the block
+// keyed `Buffer A/Params`.
+fn address(block: &str) -> String {
+    format!("/{}", block.strip_prefix("Buffer ").unwrap_or(block))
+}
+
+// Turn a message into a command for the renderer.
+fn command(address: &str, args: Vec<Arg>, from: SocketAddr) -> Result<Command, String> {
+    let parts = address
+        .strip_prefix('/')
+        .ok_or("Addresses must start with \"/\"")?
+        .split('/')
+        .collect::<Vec<_>>();
+
+    match parts.as_slice() {
+        ["preset"] => match args.as_slice() {
+            [Arg::String(name)] => Ok(Command::Preset(name.clone())),
+            _ => Err("Expected the name of a preset".to_string()),
+        },
+        ["time"] => match args.as_slice() {
+            [t] => t.as_f64().map(|t| Command::Time(t as f32)).ok_or(format!("Invalid time {}", t)),
+            _ => Err("Expected a time in seconds".to_string()),
+        },
+        ["sync"] => Ok(Command::Subscribe(from)),
+        [block, var] => Ok(Command::Set {
+            block: block.to_string(),
+            var: var.to_string(),
+            args,
+        }),
+        [pass, block, var] => Ok(Command::Set {
+            block: preset::key(Some(pass), block),
+            var: var.to_string(),
+            args,
+        }),
+        _ => Err("Unknown address".to_string()),
+    }
+}
+
+// Decode a packet into its messages as (address, arguments), flattening bundles.
+// Bundles are handled right away rather than at their time tag.
+fn decode(packet: &[u8], messages: &mut Vec<(String, Vec<Arg>)>) -> Result<(), String> {
+    let mut r = Reader { data: packet, pos: 0 };
+
+    if packet.starts_with(b"#bundle\0") {
+        r.bytes(16)?;
+        while r.pos < packet.len() {
+            let size = usize::try_from(r.i32()?).map_err(|_| "Negative bundle element size")?;
+            let element = r.bytes(size)?;
+            decode(element, messages)?;
+        }
+        return Ok(());
+    }
+
+    let address = r.string()?;
+    // Very old clients leave out the type tags when there are no arguments.
+    let tags = if r.pos < packet.len() { r.string()? } else { ",".to_string() };
+    let tags = tags.strip_prefix(',').ok_or("Missing type tags")?;
+
+    let mut args = vec![];
+    for tag in tags.chars() {
+        args.push(match tag {
+            'i' => Arg::Int(r.i32()? as i64),
+            'h' => Arg::Int(i64::from_be_bytes(r.array()?)),
+            'f' => Arg::Float(f32::from_be_bytes(r.array()?) as f64),
+            'd' => Arg::Float(f64::from_be_bytes(r.array()?)),
+            'T' => Arg::Bool(true),
+            'F' => Arg::Bool(false),
+            's' | 'S' => Arg::String(r.string()?),
+            // Nil and impulse don't carry a value.
+            'N' | 'I' => continue,
+            _ => return Err(format!("Unsupported type tag \"{}\"", tag)),
+        });
+    }
+
+    messages.push((address, args));
+    Ok(())
+}
+
+fn encode(address: &str, args: &[Arg]) -> Vec<u8> {
+    let mut tags = String::from(",");
+    let mut data = vec![];
+    for arg in args {
+        match arg {
+            Arg::Int(i) => match i32::try_from(*i) {
+                Ok(i) => {
+                    tags.push('i');
+                    data.extend_from_slice(&i.to_be_bytes());
+                }
+                Err(_) => {
+                    tags.push('h');
+                    data.extend_from_slice(&i.to_be_bytes());
+                }
+            },
+            Arg::Float(f) => {
+                tags.push('f');
+                data.extend_from_slice(&(*f as f32).to_be_bytes());
+            }
+            Arg::Bool(b) => tags.push(if *b { 'T' } else { 'F' }),
+            Arg::String(s) => {
+                tags.push('s');
+                write_string(&mut data, s);
+            }
+        }
+    }
+
+    let mut packet = vec![];
+    write_string(&mut packet, address);
+    write_string(&mut packet, &tags);
+    packet.extend(data);
+    packet
+}
+
+// Write a string with a null terminator, padded to a multiple of 4 bytes.
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(s.as_bytes());
+    out.resize(out.len() + pad(s.len() + 1) - s.len(), 0);
+}
+
+fn pad(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or("Packet ended early")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let rest = &self.data[self.pos..];
+        let len = rest.iter().position(|&b| b == 0).ok_or("Unterminated string")?;
+        let s = std::str::from_utf8(&rest[..len]).map_err(|_| "Invalid UTF-8 in string")?;
+        self.bytes(pad(len + 1))?;
+        Ok(s.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::Ipv4Addr, sync::mpsc, time::Duration};
+
+    fn decode_all(packet: &[u8]) -> Result<Vec<(String, Vec<Arg>)>, String> {
+        let mut messages = vec![];
+        decode(packet, &mut messages)?;
+        Ok(messages)
+    }
+
+    fn bundle(elements: &[Vec<u8>]) -> Vec<u8> {
+        let mut packet = b"#bundle\0".to_vec();
+        packet.extend_from_slice(&1u64.to_be_bytes());
+        for element in elements {
+            packet.extend_from_slice(&(element.len() as i32).to_be_bytes());
+            packet.extend_from_slice(element);
+        }
+        packet
+    }
+
+    #[test]
+    fn round_trip() {
+        let args = vec![
+            Arg::Int(3),
+            Arg::Int(1 << 40),
+            Arg::Float(0.5),
+            Arg::Bool(true),
+            Arg::Bool(false),
+            Arg::String("warm".to_string()),
+        ];
+        let packet = encode("/Params/tint", &args);
+        assert_eq!(packet.len() % 4, 0);
+        assert_eq!(decode_all(&packet), Ok(vec![("/Params/tint".to_string(), args)]));
+    }
+
+    #[test]
+    fn round_trip_bundle() {
+        let swirl = encode("/Params/swirl", &[Arg::Float(0.5)]);
+        let preset = encode("/preset", &[Arg::String("calm".to_string())]);
+        let nested = bundle(&[encode("/time", &[Arg::Float(2.0)])]);
+        assert_eq!(
+            decode_all(&bundle(&[swirl, preset, nested])),
+            Ok(vec![
+                ("/Params/swirl".to_string(), vec![Arg::Float(0.5)]),
+                ("/preset".to_string(), vec![Arg::String("calm".to_string())]),
+                ("/time".to_string(), vec![Arg::Float(2.0)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        // Cut off in the address, the type tags and the argument.
+        let packet = encode("/Params/swirl", &[Arg::Float(0.5)]);
+        for &n in &[0, 6, 17, 22] {
+            assert!(decode_all(&packet[..n]).is_err(), "{} bytes", n);
+        }
+
+        let packet = bundle(&[packet]);
+        assert!(decode_all(&packet[..packet.len() - 1]).is_err());
+        assert!(decode_all(&packet[..12]).is_err());
+    }
+
+    #[test]
+    fn unterminated_string() {
+        assert!(decode_all(b"/Params/swirl").is_err());
+        assert!(decode_all(b"/time\0\0\0,s\0\0name").is_err());
+    }
+
+    #[test]
+    fn negative_bundle_size() {
+        let mut packet = bundle(&[]);
+        packet.extend_from_slice(&(-8i32).to_be_bytes());
+        packet.extend_from_slice(&encode("/sync", &[]));
+        assert!(decode_all(&packet).is_err());
+    }
+
+    #[test]
+    fn loopback() {
+        let (tx, rx) = mpsc::channel();
+        let osc = Osc::spawn(IpAddr::V4(Ipv4Addr::LOCALHOST), 0, None, tx).unwrap();
+        let addr = osc.socket.local_addr().unwrap();
+        assert!(addr.ip().is_loopback());
+
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        client.send_to(&encode("/Params/swirl", &[Arg::Float(0.5)]), addr).unwrap();
+        match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            Command::Set { block, var, args } => {
+                assert_eq!(block, "Params");
+                assert_eq!(var, "swirl");
+                assert_eq!(args, vec![Arg::Float(0.5)]);
+            }
+            command => panic!("Unexpected command {:?}", command),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Drives the time constants passed to programs.
+pub struct Clock {
+    // Number of frames rendered so far.
+    frame: u32,
+    source: Source,
+}
+
+enum Source {
+    // Follows the wall clock, for rendering live. Time can be paused and
+    // moved around, so it's counted from `base` at the instant `since`.
+    Realtime {
+        since: Instant,
+        base: f32,
+        playing: bool,
+        // When the last frame finished and how long it took.
+        last: Instant,
+        delta: f32,
+    },
+    // Steps forward a fixed amount every frame regardless of how long frames
+    // take to render, so that exported frames are reproducible.
+    Fixed { start: f32, fps: f32 },
+}
+
+impl Clock {
+    pub fn realtime() -> Self {
+        let now = Instant::now();
+        Self {
+            frame: 0,
+            source: Source::Realtime {
+                since: now,
+                base: 0.0,
+                playing: true,
+                last: now,
+                delta: 0.0,
+            },
+        }
+    }
+
+    pub fn fixed(start: f32, fps: f32) -> Self {
+        Self {
+            frame: 0,
+            source: Source::Fixed { start, fps },
+        }
+    }
+
+    // Current time in seconds.
+    pub fn time(&self) -> f32 {
+        match self.source {
+            Source::Realtime {
+                since, base, playing, ..
+            } => {
+                if playing {
+                    base + since.elapsed().as_secs_f32()
+                } else {
+                    base
+                }
+            }
+            // Compute from the frame number rather than accumulating steps to
+            // avoid drifting over long ranges.
+            Source::Fixed { start, fps } => start + (self.frame as f64 / fps as f64) as f32,
+        }
+    }
+
+    // Time in seconds the last frame took.
+    pub fn delta(&self) -> f32 {
+        match self.source {
+            Source::Realtime { delta, .. } => delta,
+            Source::Fixed { fps, .. } => 1.0 / fps,
+        }
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn is_playing(&self) -> bool {
+        match self.source {
+            Source::Realtime { playing, .. } => playing,
+            Source::Fixed { .. } => true,
+        }
+    }
+
+    // Pause or resume a realtime clock.
+    pub fn set_playing(&mut self, play: bool) {
+        let time = self.time();
+        if let Source::Realtime {
+            since, base, playing, ..
+        } = &mut self.source
+        {
+            *since = Instant::now();
+            *base = time;
+            *playing = play;
+        }
+    }
+
+    // Jump a realtime clock to a time in seconds.
+    pub fn seek(&mut self, time: f32) {
+        if let Source::Realtime { since, base, .. } = &mut self.source {
+            *since = Instant::now();
+            *base = time;
+        }
+    }
+
+    // Advance to the next frame.
+    pub fn tick(&mut self) {
+        self.frame += 1;
+        if let Source::Realtime { last, delta, .. } = &mut self.source {
+            let now = Instant::now();
+            *delta = (now - *last).as_secs_f32();
+            *last = now;
+        }
+    }
+}
+
+// The current date in UTC like ShaderToy's `iDate`, as the year, month (from
+// 0), day of the month (from 1) and seconds since midnight.
+pub fn date() -> [f32; 4] {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let days = (secs / 86400.0).floor() as i64;
+
+    // Convert days since the epoch to a civil date, from Howard Hinnant's
+    // `civil_from_days` algorithm.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    [
+        year as f32,
+        (month - 1) as f32,
+        day as f32,
+        (secs - days as f64 * 86400.0) as f32,
+    ]
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    program::{compiler::Compiler, preset, Modulators, Morph, Preset, Program, Sampler, Texture, Timeline},
+    render::{clock::Clock, headless, headless::Headless},
+};
+
+// Export a range of frames of the shader in `file` on a separate thread, starting
+// from the `current` state of a program that's already running. `progress` holds
+// the frames done and the total until the export finishes.
+pub fn spawn(
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    file: &Path,
+    current: Current,
+    export: Export,
+    size: [u32; 2],
+    progress: Arc<Mutex<Option<(u32, u32)>>>,
+) {
+    let file = file.to_owned();
+    let total = export.frames();
+    *progress.lock().unwrap() = Some((0, total));
+    std::thread::spawn(move || {
+        // Compile again rather than reusing the running program, since the final
+        // pass needs to render to a different format. This happens here rather
+        // than before spawning so the GUI doesn't stall while it compiles.
+        let mut compiler = Compiler::new(Arc::clone(&device), Arc::clone(&queue));
+        let result = compiler
+            .program(&file, headless::FORMAT)
+            .map_err(|e| e.to_string())
+            .and_then(|mut program| {
+                current.apply(&device, &mut program);
+                let mut headless = Headless::with_program(device, queue, program, size);
+                export.run(&mut headless, |i| *progress.lock().unwrap() = Some((i, total)))
+            });
+        match result {
+            Ok(()) => log::info!("Wrote {} frames to {}", total, export.out.display()),
+            Err(e) => log::error!("Export failed: {}", e),
+        }
+        *progress.lock().unwrap() = None;
+    });
+}
+
+// What carries over from a running program to an export of it: the values of
+// its variables, and the images and sampler settings picked in the GUI. Unlike
+// the program, this is cheap to capture and can be sent to the export thread.
+pub struct Current {
+    preset: Preset,
+    // Keyed like blocks in presets, by pass and name.
+    textures: HashMap<String, Texture>,
+    samplers: HashMap<String, Sampler>,
+}
+
+impl Current {
+    pub fn capture(program: &Program) -> Self {
+        let mut textures = HashMap::new();
+        let mut samplers = HashMap::new();
+        for (pass, group) in program.groups() {
+            for texture in group.textures.values() {
+                textures.insert(preset::key(pass, &texture.name), texture.clone());
+            }
+            for sampler in group.samplers.values() {
+                samplers.insert(preset::key(pass, &sampler.name), sampler.clone());
+            }
+        }
+        Self {
+            preset: Preset::capture(program),
+            textures,
+            samplers,
+        }
+    }
+
+    // Apply the state to a program compiled from the same shader, the same way
+    // `Program::initialize` carries it over when reloading.
+    fn apply(&self, device: &wgpu::Device, program: &mut Program) {
+        self.preset.apply(program);
+        for (pass, group) in program.groups_mut() {
+            for texture in group.textures.values_mut() {
+                match self.textures.get(&preset::key(pass, &texture.name)) {
+                    Some(current) if texture.path.is_none() => *texture = current.clone(),
+                    _ => {}
+                }
+            }
+            for sampler in group.samplers.values_mut() {
+                if let Some(current) = self.samplers.get(&preset::key(pass, &sampler.name)) {
+                    *sampler = current.clone();
+                }
+            }
+            group.bind(device);
+        }
+    }
+}
+
+// File extensions that are encoded into a video with ffmpeg rather than
+// written out as numbered images.
+const VIDEO: [&str; 4] = ["mp4", "webm", "mkv", "mov"];
+
+// Settings for rendering a range of time to a sequence of images or a video.
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub from: f32,
+    pub to: f32,
+    pub fps: f32,
+    // Either a video file, or an image whose name is numbered for each frame,
+    // ex. `out.png` becomes `out_00000.png`, `out_00001.png`, ...
+    pub out: PathBuf,
+    // Snapshots to morph through from the first to the last over the range.
+    pub morph: Option<Morph>,
+    // Keyframes animating variables over the range.
+    pub timeline: Option<Timeline>,
+    // Modulators driving variables from the time of each frame.
+    pub modulators: Option<Modulators>,
+}
+
+impl Default for Export {
+    fn default() -> Self {
+        Self {
+            from: 0.0,
+            to: 10.0,
+            fps: 60.0,
+            out: PathBuf::from("out.mp4"),
+            morph: None,
+            timeline: None,
+            modulators: None,
+        }
+    }
+}
+
+impl Export {
+    pub fn frames(&self) -> u32 {
+        ((self.to - self.from) * self.fps).round().max(0.0) as u32
+    }
+
+    pub fn is_video(&self) -> bool {
+        let ext = self.out.extension().unwrap_or_default().to_string_lossy();
+        VIDEO.contains(&ext.to_lowercase().as_str())
+    }
+
+    // Render every frame in the range, calling `progress` with the number of
+    // frames done so far after each one.
+    pub fn run(&self, headless: &mut Headless, progress: impl FnMut(u32)) -> Result<(), String> {
+        if self.fps <= 0.0 {
+            return Err(format!("Invalid frame rate {}", self.fps));
+        }
+
+        let mut ffmpeg = if self.is_video() {
+            Some(self.ffmpeg(headless.size())?)
+        } else {
+            None
+        };
+
+        let result = self.render(headless, ffmpeg.as_mut().and_then(|f| f.stdin.as_mut()), progress);
+
+        // Close stdin so ffmpeg knows there are no more frames, then let it
+        // finish, even if rendering failed part of the way through.
+        if let Some(mut ffmpeg) = ffmpeg {
+            drop(ffmpeg.stdin.take());
+            let status = ffmpeg
+                .wait()
+                .map_err(|e| format!("Failed to wait for ffmpeg: {}", e))?;
+            if !status.success() {
+                // Failing to write a frame is usually from ffmpeg giving up.
+                return Err(match result {
+                    Ok(()) => format!("ffmpeg exited with {}", status),
+                    Err(e) => format!("{} (ffmpeg exited with {})", e, status),
+                });
+            }
+            log::info!("ffmpeg exited with {}", status);
+        }
+
+        result
+    }
+
+    // Render every frame in the range, writing them to ffmpeg if given or to
+    // numbered images otherwise.
+    fn render(
+        &self,
+        headless: &mut Headless,
+        mut ffmpeg: Option<&mut ChildStdin>,
+        mut progress: impl FnMut(u32),
+    ) -> Result<(), String> {
+        let frames = self.frames();
+        let mut modulators = self.modulators.clone();
+        let mut clock = Clock::fixed(self.from, self.fps);
+        for i in 0..frames {
+            // Sweep through the snapshots over the range.
+            if let Some(morph) = &self.morph {
+                let t = i as f32 / frames.saturating_sub(1).max(1) as f32;
+                morph.apply(&mut headless.program, t * morph.end());
+            }
+            if let Some(timeline) = &self.timeline {
+                timeline.apply(&mut headless.program, clock.time());
+            }
+            if let Some(modulators) = &mut modulators {
+                headless.update(&clock);
+                let consts = headless.program.consts;
+                modulators.apply(&mut headless.program, &consts);
+            }
+
+            let pixels = headless.render(&clock)?;
+            clock.tick();
+
+            match &mut ffmpeg {
+                Some(stdin) => stdin
+                    .write_all(&pixels)
+                    .map_err(|e| format!("Failed to write frame to ffmpeg: {}", e))?,
+                None => {
+                    let file = frame_path(&self.out, i);
+                    let [w, h] = headless.size();
+                    image::save_buffer(&file, &pixels, w, h, image::ColorType::Rgba8)
+                        .map_err(|e| format!("Failed to write \"{}\": {}", file.display(), e))?;
+                }
+            }
+
+            progress(i + 1);
+        }
+        Ok(())
+    }
+
+    // Start an ffmpeg process reading raw RGBA frames from stdin.
+    fn ffmpeg(&self, size: [u32; 2]) -> Result<Child, String> {
+        Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", size[0], size[1])])
+            .args(["-r", &self.fps.to_string()])
+            .args(["-i", "-"])
+            // Most players can't handle anything but 4:2:0 chroma subsampling.
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(&self.out)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start ffmpeg: {}", e))
+    }
+}
+
+// Number the file name of an image for a frame, ex. `out.png` -> `out_00012.png`.
+fn frame_path(out: &Path, frame: u32) -> PathBuf {
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = out.extension().map_or("png".into(), |e| e.to_string_lossy());
+    out.with_file_name(format!("{}_{:05}.{}", stem, frame, ext))
+}
@@ -1,66 +1,603 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
 use imgui::*;
 
-pub fn input_int(ui: &Ui, name: &str, v: &mut i32) {
+use crate::{
+    program::{
+        modulator::{Shape, Source},
+        timeline::Easing,
+        Diagnostic, Expr, Meta, Modulators, Morph, Presets, Sampler, State, Status, Texture, Timeline,
+        Variable,
+    },
+    render::export::Export,
+};
+
+// Show an editor for any variable, recursing into arrays and structs, set up
+// by the annotations on its declaration.
+pub fn input_variable(ui: &Ui, name: &str, var: &mut Variable, meta: &Meta) {
+    ui.group(|| match var {
+        Variable::Bool(b) => input_bool(ui, name, b),
+        Variable::Int(i) => input_int(ui, name, i, meta),
+        Variable::UInt(u) => input_uint(ui, name, u, meta),
+        Variable::Float(f) => input_float(ui, name, f, meta),
+        Variable::Vec2(v) => input_vec2(ui, name, v, meta),
+        Variable::Vec3(v) => input_vec3(ui, name, v, meta),
+        Variable::Vec4(v) => input_vec4(ui, name, v, meta),
+        Variable::IVec2(v) => input_ivec2(ui, name, v),
+        Variable::IVec3(v) => input_ivec3(ui, name, v),
+        Variable::IVec4(v) => input_ivec4(ui, name, v),
+        Variable::UVec2(v) => input_uvec(ui, name, v),
+        Variable::UVec3(v) => input_uvec(ui, name, v),
+        Variable::UVec4(v) => input_uvec(ui, name, v),
+        Variable::Mat2(m) => input_mat2(ui, name, m),
+        Variable::Mat3(m) => input_mat3(ui, name, m),
+        Variable::Mat4(m) => input_mat4(ui, name, m),
+        Variable::Array(a) => {
+            if let Some(_node) = ui.tree_node(format!("{}[{}]", name, a.elems.len())) {
+                for (i, elem) in a.elems.iter_mut().enumerate() {
+                    input_variable(ui, &format!("{}[{}]", name, i), elem, meta);
+                }
+            }
+        }
+        Variable::Struct(s) => {
+            if let Some(_node) = ui.tree_node(format!("{} ({})", name, s.name)) {
+                for (name, var) in &mut s.vars {
+                    input_variable(ui, name, var, &Meta::default());
+                }
+            }
+        }
+    });
+
+    if let Some(tooltip) = &meta.tooltip {
+        if ui.is_item_hovered() {
+            ui.tooltip_text(tooltip);
+        }
+    }
+}
+
+pub fn input_bool(ui: &Ui, name: &str, v: &mut bool) {
+    ui.checkbox(name, v);
+}
+
+pub fn input_int(ui: &Ui, name: &str, v: &mut i32, meta: &Meta) {
     ui.input_int(name.to_string(), v)
+        .step(meta.step.map_or(1, |s| s as i32))
+        .build();
+
+    if let Some([min, max]) = meta.range {
+        let id = ui.push_id(format!("{}.fr", name));
+        ui.slider_config("", min as i32, max as i32)
+            .flags(slider_flags(meta))
+            .build(v);
+        id.pop();
+    }
+}
+
+pub fn input_uint(ui: &Ui, name: &str, v: &mut u32, meta: &Meta) {
+    ui.input_scalar(name.to_string(), v)
+        .step(meta.step.map_or(1, |s| s as u32))
+        .build();
+
+    if let Some([min, max]) = meta.range {
+        let id = ui.push_id(format!("{}.fr", name));
+        ui.slider_config("", min as u32, max as u32)
+            .flags(slider_flags(meta))
+            .build(v);
+        id.pop();
+    }
+
+    // Show the individual bits too, since unsigned ints are mostly used as flags.
+    ui.text(format!("{:#034b}", v));
+}
+
+pub fn input_ivec2(ui: &Ui, name: &str, v: &mut [i32; 2]) {
+    ui.input_int2(name.to_string(), v)
+        .build();
+}
+
+pub fn input_ivec3(ui: &Ui, name: &str, v: &mut [i32; 3]) {
+    ui.input_int3(name.to_string(), v)
+        .build();
+}
+
+pub fn input_ivec4(ui: &Ui, name: &str, v: &mut [i32; 4]) {
+    ui.input_int4(name.to_string(), v)
         .build();
 }
 
-pub fn input_float(ui: &Ui, name: &str, v: &mut f32) {
+pub fn input_uvec(ui: &Ui, name: &str, v: &mut [u32]) {
+    ui.input_scalar_n(name.to_string(), v)
+        .build();
+}
+
+pub fn input_float(ui: &Ui, name: &str, v: &mut f32, meta: &Meta) {
     ui.input_float(name.to_string(), v)
+        .step(meta.step.unwrap_or(0.0))
         .build();
 
     let id = ui.push_id(format!("{}.fr", name));
-    ui.slider("", 0.0, 1.0, v);
+    slider(ui, "", v, meta);
     id.pop();
 }
 
-pub fn input_vec2(ui: &Ui, name: &str, v: &mut [f32; 2]) {
+pub fn input_vec2(ui: &Ui, name: &str, v: &mut [f32; 2], meta: &Meta) {
     ui.input_float2(name.to_string(), v)
         .build();
 
     let id = ui.push_id(format!("{}.x", name));
-    ui.slider("x", 0.0, 1.0, &mut v[0]);
+    slider(ui, "x", &mut v[0], meta);
     id.pop();
 
     let id = ui.push_id(format!("{}.y", name));
-    ui.slider("y", 0.0, 1.0, &mut v[1]);
+    slider(ui, "y", &mut v[1], meta);
     id.pop();
 }
 
-pub fn input_vec3(ui: &Ui, name: &str, v: &mut [f32; 3]) {
+pub fn input_vec3(ui: &Ui, name: &str, v: &mut [f32; 3], meta: &Meta) {
+    if meta.color {
+        ui.color_edit3(name, v);
+        return;
+    }
+
     ui.input_float3(name.to_string(), v)
         .build();
 
     let id = ui.push_id(format!("{}.x", name));
-    ui.slider("x", 0.0, 1.0, &mut v[0]);
+    slider(ui, "x", &mut v[0], meta);
     id.pop();
 
     let id = ui.push_id(format!("{}.y", name));
-    ui.slider("y", 0.0, 1.0, &mut v[1]);
+    slider(ui, "y", &mut v[1], meta);
     id.pop();
 
     let id = ui.push_id(format!("{}.z", name));
-    ui.slider("z", 0.0, 1.0, &mut v[2]);
+    slider(ui, "z", &mut v[2], meta);
     id.pop();
 }
 
-pub fn input_vec4(ui: &Ui, name: &str, v: &mut [f32; 4]) {
+pub fn input_vec4(ui: &Ui, name: &str, v: &mut [f32; 4], meta: &Meta) {
+    if meta.color {
+        ui.color_edit4(name, v);
+        return;
+    }
+
     ui.input_float4(name.to_string(), v)
         .build();
 
     let id = ui.push_id(format!("{}.x", name));
-    ui.slider("x", 0.0, 1.0, &mut v[0]);
+    slider(ui, "x", &mut v[0], meta);
     id.pop();
 
     let id = ui.push_id(format!("{}.y", name));
-    ui.slider("y", 0.0, 1.0, &mut v[1]);
+    slider(ui, "y", &mut v[1], meta);
     id.pop();
 
     let id = ui.push_id(format!("{}.z", name));
-    ui.slider("z", 0.0, 1.0, &mut v[2]);
+    slider(ui, "z", &mut v[2], meta);
     id.pop();
 
     let id = ui.push_id(format!("{}.w", name));
-    ui.slider("w", 0.0, 1.0, &mut v[2]);
+    slider(ui, "w", &mut v[3], meta);
+    id.pop();
+}
+
+// A slider over the annotated range of a variable, or from 0 to 1.
+fn slider(ui: &Ui, label: &str, v: &mut f32, meta: &Meta) -> bool {
+    let [min, max] = meta.range.unwrap_or([0.0, 1.0]);
+    ui.slider_config(label, min, max)
+        .flags(slider_flags(meta))
+        .build(v)
+}
+
+fn slider_flags(meta: &Meta) -> SliderFlags {
+    if meta.log {
+        SliderFlags::LOGARITHMIC
+    } else {
+        SliderFlags::empty()
+    }
+}
+
+// Edit a matrix one column per row, since matrices are stored column-major.
+pub fn input_mat2(ui: &Ui, name: &str, m: &mut [[f32; 2]; 2]) {
+    ui.text(name);
+    for (i, c) in m.iter_mut().enumerate() {
+        ui.input_float2(format!("{}##{}.{}", i, name, i), c)
+            .build();
+    }
+}
+
+pub fn input_mat3(ui: &Ui, name: &str, m: &mut [[f32; 3]; 3]) {
+    ui.text(name);
+    for (i, c) in m.iter_mut().enumerate() {
+        ui.input_float3(format!("{}##{}.{}", i, name, i), c)
+            .build();
+    }
+}
+
+pub fn input_mat4(ui: &Ui, name: &str, m: &mut [[f32; 4]; 4]) {
+    ui.text(name);
+    for (i, c) in m.iter_mut().enumerate() {
+        ui.input_float4(format!("{}##{}.{}", i, name, i), c)
+            .build();
+    }
+}
+
+// Show a texture along with a field to type in the path of an image to load.
+// Returns true if the image at `path` should be loaded.
+pub fn input_texture(ui: &Ui, texture: &Texture, path: &mut String) -> bool {
+    ui.text(format!("{} ({}x{})", texture.name, texture.size[0], texture.size[1]));
+
+    let id = ui.push_id(&texture.name);
+    ui.input_text("##path", path).build();
+    ui.same_line();
+    let load = ui.button("Load");
+    id.pop();
+
+    load
+}
+
+// Show the filter and wrap modes of a sampler. Returns true if either changed.
+pub fn input_sampler(ui: &Ui, sampler: &mut Sampler) -> bool {
+    const FILTERS: [(wgpu::FilterMode, &str); 2] = [
+        (wgpu::FilterMode::Nearest, "Nearest"),
+        (wgpu::FilterMode::Linear, "Linear"),
+    ];
+    const WRAPS: [(wgpu::AddressMode, &str); 3] = [
+        (wgpu::AddressMode::Repeat, "Repeat"),
+        (wgpu::AddressMode::MirrorRepeat, "Mirror"),
+        (wgpu::AddressMode::ClampToEdge, "Clamp"),
+    ];
+
+    ui.text(&sampler.name);
+    let id = ui.push_id(&sampler.name);
+
+    let mut filter = FILTERS.iter().position(|(f, _)| *f == sampler.filter).unwrap_or(0);
+    let mut wrap = WRAPS.iter().position(|(w, _)| *w == sampler.wrap).unwrap_or(0);
+    let mut changed = ui.combo("Filter", &mut filter, &FILTERS, |(_, s)| (*s).into());
+    changed |= ui.combo("Wrap", &mut wrap, &WRAPS, |(_, s)| (*s).into());
+    sampler.filter = FILTERS[filter].0;
+    sampler.wrap = WRAPS[wrap].0;
+
     id.pop();
+    changed
+}
+
+// Show the settings for exporting a range of frames, or the progress of the
+// running export as (frames done, total). Returns true if an export should start.
+pub fn input_export(
+    ui: &Ui,
+    export: &mut Export,
+    size: &mut [u32; 2],
+    path: &mut String,
+    progress: Option<(u32, u32)>,
+) -> bool {
+    ui.input_float("From", &mut export.from).build();
+    ui.input_float("To", &mut export.to).build();
+    ui.input_float("FPS", &mut export.fps).build();
+    export.fps = export.fps.max(1.0);
+
+    let mut s = [size[0] as i32, size[1] as i32];
+    if ui.input_int2("Size", &mut s).build() {
+        *size = [s[0].max(1) as u32, s[1].max(1) as u32];
+    }
+
+    ui.input_text("Output", path).build();
+    export.out = path.as_str().into();
+
+    match progress {
+        Some((done, total)) => {
+            ProgressBar::new(done as f32 / total.max(1) as f32)
+                .overlay_text(format!("{}/{}", done, total))
+                .build(ui);
+            false
+        }
+        None => {
+            ui.text(format!("{} frames", export.frames()));
+            ui.same_line();
+            ui.button("Export")
+        }
+    }
+}
+
+// Show the saved presets along with a name to save the current values under.
+// Clicking a preset picks its name and loads it. Returns which of (load, save,
+// delete) should happen to the preset named `name`.
+pub fn input_presets(ui: &Ui, presets: &Presets, name: &mut String) -> (bool, bool, bool) {
+    let mut load = false;
+    for preset in presets.names() {
+        if ui.selectable_config(preset).selected(preset == name).build() {
+            *name = preset.to_string();
+            load = true;
+        }
+    }
+    if presets.names().next().is_none() {
+        ui.text_disabled("No presets saved yet");
+    }
+    ui.separator();
+
+    ui.input_text("Name", name).build();
+    let valid = !name.trim().is_empty();
+    let save = ui.button("Save") && valid;
+    ui.same_line();
+    let delete = ui.button("Delete") && valid;
+    (load, save, delete)
+}
+
+// Show the snapshots to morph between along with a crossfader, and whether to
+// morph through them in exports. Returns whether to capture a new snapshot,
+// and whether the crossfader moved so the blend should be applied.
+pub fn input_morph(ui: &Ui, morph: &mut Morph, export: &mut bool) -> (bool, bool) {
+    let mut remove = None;
+    for (i, snapshot) in morph.snapshots.iter().enumerate() {
+        let _id = ui.push_id_usize(i);
+        ui.text(&snapshot.name);
+        ui.same_line();
+        if ui.small_button("Remove") {
+            remove = Some(i);
+        }
+    }
+    if let Some(i) = remove {
+        morph.snapshots.remove(i);
+        morph.position = morph.position.min(morph.end());
+    }
+    if morph.snapshots.is_empty() {
+        ui.text_disabled("No snapshots yet");
+    }
+    ui.separator();
+
+    let capture = ui.button("Capture");
+    let mut changed = false;
+    if morph.snapshots.len() > 1 {
+        changed = ui.slider("Crossfade", 0.0, morph.end(), &mut morph.position);
+        ui.checkbox("Morph in exports", export);
+    }
+    (capture, changed)
+}
+
+// Show the playback controls and keyframes of a timeline. `vars` names every
+// variable that can be animated, of which `selected` is picked for adding a
+// keyframe. Returns whether to (seek to `time`, add a keyframe, save).
+pub fn input_timeline(
+    ui: &Ui,
+    timeline: &mut Timeline,
+    time: &mut f32,
+    playing: &mut bool,
+    vars: &[String],
+    selected: &mut usize,
+) -> (bool, bool, bool) {
+    if ui.button(if *playing { "Pause" } else { "Play" }) {
+        *playing = !*playing;
+    }
+    ui.same_line();
+    ui.checkbox("Loop", &mut timeline.looping);
+
+    let [start, end] = timeline.range;
+    let seek = ui.slider("Time", start, end.max(start), time);
+    ui.input_float2("Range", &mut timeline.range).build();
+    ui.separator();
+
+    ui.combo_simple_string("Variable", selected, vars);
+    let key = ui.button("Add Key") && *selected < vars.len();
+    ui.separator();
+
+    for (i, track) in timeline.tracks.iter_mut().enumerate() {
+        let _id = ui.push_id_usize(i);
+        let label = format!("{}.{} ({})", track.block, track.var, track.keys.len());
+        if let Some(_node) = ui.tree_node(label) {
+            let mut remove = None;
+            let mut moved = false;
+            for (j, key) in track.keys.iter_mut().enumerate() {
+                let _id = ui.push_id_usize(j);
+                ui.set_next_item_width(50.0);
+                moved |= ui.input_float("##time", &mut key.time).build();
+                ui.same_line();
+                ui.set_next_item_width(90.0);
+                input_easing(ui, &mut key.easing);
+                ui.same_line();
+                if ui.small_button("x") {
+                    remove = Some(j);
+                }
+            }
+
+            if moved {
+                track.keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+            }
+            if let Some(j) = remove {
+                track.keys.remove(j);
+            }
+        }
+    }
+    timeline.tracks.retain(|track| !track.keys.is_empty());
+    if timeline.tracks.is_empty() {
+        ui.text_disabled("No keyframes yet");
+    }
+    ui.separator();
+
+    let save = ui.button("Save");
+    (seek, key, save)
+}
+
+// Show the easing out of a keyframe, along with the control points of a bezier curve.
+fn input_easing(ui: &Ui, easing: &mut Easing) {
+    const EASINGS: [&str; 3] = ["Linear", "Smoothstep", "Bezier"];
+
+    let mut i = match easing {
+        Easing::Linear => 0,
+        Easing::Smoothstep => 1,
+        Easing::Bezier(_) => 2,
+    };
+    if ui.combo_simple_string("##easing", &mut i, &EASINGS) {
+        *easing = match i {
+            0 => Easing::Linear,
+            1 => Easing::Smoothstep,
+            // Start out easing in and out.
+            _ => Easing::Bezier([0.42, 0.0, 0.58, 1.0]),
+        };
+    }
+
+    if let Easing::Bezier(points) = easing {
+        ui.input_float4("##bezier", points).build();
+    }
+}
+
+// Show the modulators driving variables and the tempo for `beat` in
+// expressions. `targets` names every variable that can be modulated, of which
+// `selected` is picked for adding a modulator to. Returns whether to add an
+// (LFO, expression).
+pub fn input_modulators(
+    ui: &Ui,
+    modulators: &mut Modulators,
+    targets: &[String],
+    selected: &mut usize,
+) -> (bool, bool) {
+    ui.combo_simple_string("Variable", selected, targets);
+    let valid = *selected < targets.len();
+    let lfo = ui.button("Add LFO") && valid;
+    ui.same_line();
+    let expression = ui.button("Add Expression") && valid;
+    ui.separator();
+
+    let mut remove = None;
+    for (i, modulator) in modulators.list.iter_mut().enumerate() {
+        let _id = ui.push_id_usize(i);
+        ui.text(modulator.target());
+        ui.same_line();
+        if ui.small_button("Remove") {
+            remove = Some(i);
+        }
+
+        match &mut modulator.source {
+            Source::Lfo {
+                shape,
+                rate,
+                depth,
+                phase,
+                center,
+            } => {
+                let names = Shape::ALL.map(|s| s.name());
+                let mut j = Shape::ALL.iter().position(|s| s == shape).unwrap_or(0);
+                if ui.combo_simple_string("Shape", &mut j, &names) {
+                    *shape = Shape::ALL[j];
+                }
+                ui.input_float("Rate", rate).step(0.1).build();
+                ui.input_float("Depth", depth).step(0.1).build();
+                ui.input_float("Phase", phase).step(0.1).build();
+                ui.input_float("Center", center).step(0.1).build();
+            }
+            Source::Expression { text, expr } => {
+                if ui.input_text("##expr", text).build() {
+                    *expr = Expr::parse(text);
+                }
+            }
+        }
+        if let Some(e) = &modulator.error {
+            ui.text_colored(RED, e);
+        }
+        ui.separator();
+    }
+    if let Some(i) = remove {
+        modulators.list.remove(i);
+    }
+    if modulators.list.is_empty() {
+        ui.text_disabled("No modulators yet");
+        ui.separator();
+    }
+
+    ui.input_float("BPM", &mut modulators.bpm).build();
+    (lfo, expression)
+}
+
+const RED: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+// Show the state of the program, how long the last compile took and a hash of
+// the sources the running program was compiled from.
+pub fn status(ui: &Ui, status: &Status) {
+    match status.state {
+        State::Compiling => ui.text("Status: Compiling..."),
+        State::Ok => ui.text("Status: OK"),
+        State::Failed(_) => ui.text_colored(RED, "Status: Failed"),
+    }
+    if let Some(since) = status.stale_since {
+        ui.text_colored(RED, format!("Stale for {}s", since.elapsed().as_secs()));
+    }
+    if let Some(time) = status.compile_time {
+        ui.text(format!("Compile Time: {:.0}ms", time.as_secs_f64() * 1000.0));
+    }
+    if let Some(hash) = status.hash {
+        ui.text(format!("Source Hash: {:016x}", hash));
+    }
+}
+
+// Show compile errors, and whether the last program that compiled is still
+// running. Clicking an error copies its location to the clipboard.
+pub fn diagnostics(ui: &Ui, diagnostics: &[Diagnostic], running: bool) {
+    if running {
+        ui.text_colored(RED, "Compile failed, showing last good program");
+    } else {
+        ui.text_colored(RED, "Compile failed");
+    }
+    ui.separator();
+
+    for (i, diagnostic) in diagnostics.iter().enumerate() {
+        let location = diagnostic.location();
+        let mut label = match (&diagnostic.file, diagnostic.line, diagnostic.column) {
+            (Some(file), Some(line), Some(column)) => format!("{}:{}:{}: ", name(file), line, column),
+            (Some(file), Some(line), None) => format!("{}:{}: ", name(file), line),
+            (Some(file), None, _) => format!("{}: ", name(file)),
+            _ => String::new(),
+        };
+        label.push_str(&diagnostic.message);
+
+        let _id = ui.push_id_usize(i);
+        if ui.selectable(&label) {
+            if let Some(location) = &location {
+                copy(ui, location);
+            }
+        }
+        if let Some(location) = &location {
+            if ui.is_item_hovered() {
+                ui.tooltip_text(format!("Click to copy \"{}\"", location));
+            }
+        }
+    }
+}
+
+fn name(file: &std::path::Path) -> String {
+    file.file_name().unwrap_or(file.as_os_str()).to_string_lossy().into_owned()
+}
+
+// Copy text to the clipboard. Without a clipboard backend imgui only keeps it
+// to itself, so also pipe it to the platform's clipboard tool.
+fn copy(ui: &Ui, text: &str) {
+    ui.set_clipboard_text(text);
+
+    let tool: &[&str] = if cfg!(target_os = "windows") {
+        &["clip"]
+    } else if cfg!(target_os = "macos") {
+        &["pbcopy"]
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        &["wl-copy"]
+    } else {
+        &["xclip", "-selection", "clipboard"]
+    };
+
+    let child = Command::new(tool[0])
+        .args(&tool[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            // Some tools stay around to serve the clipboard, so don't block on them.
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => log::warn!("Failed to run \"{}\" to copy to the clipboard: {}", tool[0], e),
+    }
 }
@@ -0,0 +1,173 @@
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    program::{compiler::Compiler, Program},
+    render::clock::Clock,
+};
+
+// Render to an 8 bit RGBA texture so the pixels can be written straight to an
+// image file. Like the swapchain this is linear, so images match the window.
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+// Renders a program to an offscreen texture without a window or surface, and
+// reads the results back to the CPU.
+pub struct Headless {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+
+    size: [u32; 2],
+    target: wgpu::Texture,
+    readback: wgpu::Buffer,
+
+    pub program: Program,
+}
+
+impl Headless {
+    // Set up a device and compile the program in `file`, rendering at `size`.
+    pub fn new(file: &Path, size: [u32; 2], fallback: bool) -> Result<Self, String> {
+        let (_, adapter, device, queue) = super::request_device(fallback)?;
+        log::info!("Using adapter {:?}", adapter.get_info());
+
+        let mut compiler = Compiler::new(Arc::clone(&device), Arc::clone(&queue));
+        let program = compiler.program(file, FORMAT).map_err(|e| e.to_string())?;
+
+        Ok(Self::with_program(device, queue, program, size))
+    }
+
+    // Render an already compiled program, whose final pass must render to `FORMAT`.
+    pub fn with_program(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        mut program: Program,
+        size: [u32; 2],
+    ) -> Self {
+        // Start buffers out empty, even if they were carried over from another
+        // program, so every render of the same frames comes out the same.
+        for buffer in &mut program.buffers {
+            buffer.resize(size);
+        }
+
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("headless"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let readback = readback_buffer(&device, size);
+
+        Self {
+            device,
+            queue,
+
+            size,
+            target,
+            readback,
+
+            program,
+        }
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    // Update the program constants for the current frame of the clock. The
+    // date is left alone so that renders don't depend on when they happen.
+    pub fn update(&mut self, clock: &Clock) {
+        let (w, h) = (self.size[0] as f32, self.size[1] as f32);
+        self.program.consts.t = clock.time();
+        self.program.consts.dt = clock.delta();
+        self.program.consts.frame = clock.frame();
+        self.program.consts.resolution = [w, h];
+        self.program.consts.aspect = w / h;
+    }
+
+    // Render the current frame of the clock, returning the tightly packed RGBA pixels.
+    pub fn render(&mut self, clock: &Clock) -> Result<Vec<u8>, String> {
+        self.update(clock);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("headless"),
+            });
+
+        let view = self.target.create_view(&Default::default());
+        super::render_passes(&self.queue, &mut self.program, self.size, &view, &mut encoder);
+
+        copy_texture(&mut encoder, &self.target, &self.readback);
+        self.queue.submit(Some(encoder.finish()));
+        read_pixels(&self.device, &self.readback, self.size)
+    }
+
+    // Render a single frame at time `t` and save it as a PNG.
+    pub fn save(&mut self, t: f32, file: &Path) -> Result<(), String> {
+        // Pretend to be running at 60 FPS for the frame delta.
+        let pixels = self.render(&Clock::fixed(t, 60.0))?;
+        image::save_buffer(file, &pixels, self.size[0], self.size[1], image::ColorType::Rgba8)
+            .map_err(|e| format!("Failed to write \"{}\": {}", file.display(), e))
+    }
+}
+
+// Create a buffer for reading back a texture of the given size, padded to a
+// multiple of 256 bytes per row as required for copies.
+pub fn readback_buffer(device: &wgpu::Device, size: [u32; 2]) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback"),
+        size: (padded_row(size[0]) * size[1]) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    })
+}
+
+// Copy an 8 bit, 4 channel texture into a readback buffer.
+pub fn copy_texture(encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture, buffer: &wgpu::Buffer) {
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row(texture.width())),
+                rows_per_image: Some(texture.height()),
+            },
+        },
+        texture.size(),
+    );
+}
+
+// Wait for a copy into a readback buffer to finish, returning the tightly
+// packed pixels.
+pub fn read_pixels(device: &wgpu::Device, buffer: &wgpu::Buffer, size: [u32; 2]) -> Result<Vec<u8>, String> {
+    let slice = buffer.slice(..);
+    let (tx, rx) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |r| tx.send(r).unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    rx.recv()
+        .unwrap()
+        .map_err(|e| format!("Failed to read back frame: {}", e))?;
+
+    // Strip the padding from the end of each row
+    let pixels = slice
+        .get_mapped_range()
+        .chunks(padded_row(size[0]) as usize)
+        .flat_map(|r| &r[..size[0] as usize * 4])
+        .copied()
+        .collect();
+    buffer.unmap();
+
+    Ok(pixels)
+}
+
+fn padded_row(width: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * 4).div_ceil(align) * align
+}
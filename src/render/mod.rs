@@ -1,12 +1,27 @@
 use std::{
-    sync::{mpsc::Receiver, Arc, Mutex},
-    time::Instant,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc, Mutex,
+    },
 };
 use winit::dpi::PhysicalSize;
 
+mod clock;
+pub mod export;
 mod gui;
-
-use crate::program::{Constants, Program, Variable};
+pub mod headless;
+
+use crate::{
+    program::{
+        buffer, modulator, modulator::Source, preset, watcher::ImageWatcher, Constants, Modulator,
+        Modulators, Morph, Preset, Presets, Program, Snapshot, State, Status, Texture, Timeline,
+    },
+    remote::{self, osc::Osc, Command},
+};
+use clock::Clock;
+use export::Export;
 
 // Use a linear (not sRGB) format for the swapchain images  to allow shaders
 // to work in linear color space without having to perform gamma correction
@@ -23,15 +38,61 @@ pub struct Renderer {
     _instance: wgpu::Instance,
     _adapter: wgpu::Adapter,
     pub device: Arc<wgpu::Device>,
-    queue: wgpu::Queue,
+    pub queue: Arc<wgpu::Queue>,
 
     size: PhysicalSize<u32>,
     surface: wgpu::Surface,
+    // Usages of the swapchain images, which can be copied from for screenshots
+    // if the surface supports it.
+    surface_usage: wgpu::TextureUsages,
 
-    start: Instant,
+    clock: Clock,
     mouse_click: [f32; 2],
 
-    program: Arc<Mutex<Option<Program>>>,
+    pub program: Arc<Mutex<Option<Program>>>,
+    // Status of the program, updated by the compiler thread. When a compile
+    // fails the last program that compiled keeps rendering.
+    pub status: Arc<Mutex<Status>>,
+
+    // Watches image files backing textures, along with the image path typed
+    // into the GUI for each texture.
+    images: ImageWatcher,
+    image_paths: HashMap<String, String>,
+
+    // The shader file, which is compiled again for exports, along with the
+    // export settings picked in the GUI and the progress of a running export.
+    // Switching files goes through `compile_tx` to the compiler thread.
+    file: PathBuf,
+    compile_tx: Sender<Option<PathBuf>>,
+    export: Export,
+    export_size: [u32; 2],
+    export_path: String,
+    export_progress: Arc<Mutex<Option<(u32, u32)>>>,
+
+    // Named presets saved for the shader file, and the name picked in the GUI.
+    presets: Presets,
+    preset_name: String,
+
+    // Snapshots of uniform values to blend between, and whether exports
+    // should morph through them.
+    morph: Morph,
+    morph_export: bool,
+
+    // Keyframes animating variables over time, and the variable picked in the
+    // GUI for adding keyframes to.
+    timeline: Timeline,
+    timeline_var: usize,
+
+    // LFOs and expressions driving variables, and the variable picked in the
+    // GUI for adding modulators to.
+    modulators: Modulators,
+    modulator_var: usize,
+
+    // Commands from remote controllers, the OSC server if one is running to
+    // send values back to, and requests for a screenshot of the next frame.
+    remote: Receiver<Command>,
+    osc: Option<Osc>,
+    screenshots: Vec<Sender<Result<image::RgbaImage, String>>>,
 
     imgui: imgui::Context,
     imgui_plaf: imgui_winit_support::WinitPlatform,
@@ -41,38 +102,24 @@ pub struct Renderer {
 impl Renderer {
     pub fn new<T: 'static>(
         event_loop: &winit::event_loop::EventLoopWindowTarget<T>,
+        file: &Path,
         rx: Receiver<Program>,
+        compile_tx: Sender<Option<PathBuf>>,
+        preset: Option<Preset>,
+        remote: Receiver<Command>,
+        osc: Option<Osc>,
     ) -> Self {
         // Create the wgpu instance and request an adapter and device
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: None,
-            force_fallback_adapter: false,
-        }))
-        .expect("Failed to create graphics adapter!");
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: Some("device"),
-                features: wgpu::Features::PUSH_CONSTANTS,
-                limits: wgpu::Limits {
-                    max_push_constant_size: Constants::SIZE,
-                    ..std::default::Default::default()
-                },
-            },
-            None,
-        ))
-        .expect("Failed to create graphics device!");
-        let device = Arc::new(device);
+        let (instance, adapter, device, queue) =
+            request_device(false).unwrap_or_else(|e| panic!("{}", e));
 
         // Set up the winit window and swapchain
         let window = winit::window::Window::new(event_loop).unwrap();
         let size = window.inner_size();
         let surface = unsafe { instance.create_surface(&window).unwrap() };
-        Self::resize_surface(&device, &surface, size);
+        let surface_usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (surface.get_capabilities(&adapter).usages & wgpu::TextureUsages::COPY_SRC);
+        Self::resize_surface(&device, &surface, size, surface_usage);
 
         // Spawn a thread to listen for and replace the shader program with newly compiled ones
         let program: Arc<Mutex<Option<Program>>> = Arc::new(Mutex::new(None));
@@ -81,9 +128,12 @@ impl Renderer {
             let mut new = rx.recv().unwrap();
             let mut program = program.lock().unwrap();
 
-            // Copy variables from the old if existing
+            // Copy variables from the old if existing, or start with the
+            // preset picked on the command line
             if let Some(old) = program.take() {
                 new.initialize(&old);
+            } else if let Some(preset) = &preset {
+                preset.apply(&mut new);
             }
 
             // Swap in the new
@@ -132,11 +182,44 @@ impl Renderer {
 
             size,
             surface,
+            surface_usage,
 
-            start: Instant::now(),
+            clock: Clock::realtime(),
             mouse_click: [size.width as f32 / 2.0, size.height as f32 / 2.0],
 
             program: self_program,
+            status: Arc::new(Mutex::new(Status::new(file))),
+
+            images: ImageWatcher::new(),
+            image_paths: HashMap::new(),
+
+            file: file.to_owned(),
+            compile_tx,
+            export: Export::default(),
+            export_size: [1280, 720],
+            export_path: Export::default().out.display().to_string(),
+            export_progress: Arc::new(Mutex::new(None)),
+            presets: Presets::load(file).unwrap_or_else(|e| {
+                log::error!("{}", e);
+                Presets::empty(file)
+            }),
+            preset_name: String::new(),
+
+            morph: Morph::default(),
+            morph_export: false,
+
+            timeline: Timeline::load(file).unwrap_or_else(|e| {
+                log::error!("{}", e);
+                Timeline::empty(file)
+            }),
+            timeline_var: 0,
+
+            modulators: Modulators::default(),
+            modulator_var: 0,
+
+            remote,
+            osc,
+            screenshots: vec![],
 
             imgui,
             imgui_plaf,
@@ -149,9 +232,101 @@ impl Renderer {
             self.mouse_click = self.imgui.io().mouse_pos;
         }
 
+        // Jump back to the start of the timeline's range when looping
+        let [start, end] = self.timeline.range;
+        if self.timeline.looping && end > start && self.clock.time() >= end {
+            self.clock.seek(start);
+        }
+
+        // Handle commands from remote controllers
+        let commands = self.remote.try_iter().collect::<Vec<_>>();
+        for command in commands {
+            self.command(command);
+        }
+
+        if let Some(program) = self.program.lock().unwrap().as_mut() {
+            // Send back any values that changed
+            if let Some(osc) = &mut self.osc {
+                osc.send(program);
+            }
+
+            // Reload any textures whose image changed on disk
+            let device = Arc::clone(&program.device);
+            let changed = self.images.changed();
+            for (_, group) in program.groups_mut() {
+                let mut reloaded = false;
+                for texture in group.textures.values_mut() {
+                    if let Some(path) = texture.path.clone() {
+                        self.images.watch(&path);
+
+                        if changed.contains(&path) {
+                            match texture.reload(&device, &self.queue) {
+                                Ok(()) => log::info!("Image \"{}\" reloaded.", path.display()),
+                                Err(e) => log::error!("{}", e),
+                            }
+                            reloaded = true;
+                        }
+                    }
+                }
+
+                if reloaded {
+                    group.bind(&device);
+                }
+            }
+        }
+
         self.window.request_redraw();
     }
 
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::Time(t) => self.clock.seek(t),
+            Command::Subscribe(addr) => {
+                if let Some(osc) = &mut self.osc {
+                    osc.subscribe(addr);
+                }
+            }
+            Command::Open(file) => self.open(file),
+            Command::Screenshot(tx) => self.screenshots.push(tx),
+            Command::Set { block, var, args } => {
+                self.with_program(|program| remote::set(program, &block, &var, &args))
+            }
+            Command::Preset(name) => {
+                self.with_program(|program| Preset::find(&self.file, &name).map(|preset| preset.apply(program)))
+            }
+        }
+    }
+
+    // Change the running program, logging what went wrong if anything.
+    fn with_program(&self, f: impl FnOnce(&mut Program) -> Result<(), String>) {
+        match self.program.lock().unwrap().as_mut() {
+            Some(program) => {
+                if let Err(e) = f(program) {
+                    log::warn!("{}", e);
+                }
+            }
+            None => log::warn!("No program is running"),
+        }
+    }
+
+    // Switch to another shader file, along with its presets and timeline. The
+    // current program keeps running until the new file compiles.
+    fn open(&mut self, file: PathBuf) {
+        log::info!("Opening \"{}\"", file.display());
+        self.presets = Presets::load(&file).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            Presets::empty(&file)
+        });
+        self.preset_name.clear();
+        self.timeline = Timeline::load(&file).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            Timeline::empty(&file)
+        });
+        self.image_paths.clear();
+        self.compile_tx.send(Some(file.clone())).unwrap();
+        self.file = file;
+    }
+
     pub fn event<T>(&mut self, event: &winit::event::Event<T>) {
         self.imgui_plaf
             .handle_event(self.imgui.io_mut(), &self.window, event);
@@ -170,17 +345,44 @@ impl Renderer {
                 label: Some("main"),
             });
 
-        self.render_program(&self.queue, &view, &mut encoder);
+        let queue = Arc::clone(&self.queue);
+        self.render_program(&queue, &view, &mut encoder);
+
+        // Copy the frame before the GUI is drawn over it for any screenshots
+        let size = [self.size.width, self.size.height];
+        let screenshots = std::mem::take(&mut self.screenshots);
+        let readback = if screenshots.is_empty() || !self.surface_usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            None
+        } else {
+            let buffer = headless::readback_buffer(&self.device, size);
+            headless::copy_texture(&mut encoder, &frame.texture, &buffer);
+            Some(buffer)
+        };
+
         self.render_gui(&view, &mut encoder);
 
         self.queue.submit(Some(encoder.finish()));
+        if !screenshots.is_empty() {
+            let image = match readback {
+                Some(buffer) => headless::read_pixels(&self.device, &buffer, size).map(|mut pixels| {
+                    // Swap the swapchain's BGRA around to RGBA
+                    pixels.chunks_mut(4).for_each(|p| p.swap(0, 2));
+                    image::RgbaImage::from_raw(size[0], size[1], pixels).unwrap()
+                }),
+                None => Err("Screenshots aren't supported by the window surface".to_string()),
+            };
+            for tx in screenshots {
+                let _ = tx.send(image.clone());
+            }
+        }
         frame.present();
+        self.clock.tick();
 
         self.window.request_redraw();
     }
 
     fn render_program(
-        &self,
+        &mut self,
         queue: &wgpu::Queue,
         target: &wgpu::TextureView,
         encoder: &mut wgpu::CommandEncoder,
@@ -188,63 +390,24 @@ impl Renderer {
         if let Some(program) = self.program.lock().unwrap().as_mut() {
             // Update the program constants
             let (w, h) = (self.size.width as f32, self.size.height as f32);
-            program.consts.t = self.start.elapsed().as_secs_f32();
+            program.consts.t = self.clock.time();
+            program.consts.dt = self.clock.delta();
+            program.consts.frame = self.clock.frame();
+            program.consts.date = clock::date();
             program.consts.resolution = [w, h];
             program.consts.aspect = w / h;
             program.consts.mpos = Self::transform(self.size, self.imgui.io().mouse_pos);
             program.consts.mclick = Self::transform(self.size, self.mouse_click);
 
-            // Update the program uniforms
-            // TODO: Don't need to do this every frame.
-            for group in program.uniform_groups.values() {
-                for uniform in group.uniforms.values() {
-                    uniform.write(queue);
-                }
-            }
+            // Animate variables with keyframes
+            self.timeline.apply(program, program.consts.t);
 
-            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: target,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            pass.set_pipeline(&program.pipeline);
-
-            // Bind each uniform group
-            for (i, group) in &program.uniform_groups {
-                pass.set_bind_group(*i, &group.bind_group, &[]);
-            }
+            // Then drive variables from their modulators
+            let consts = program.consts;
+            self.modulators.apply(program, &consts);
 
-            // Copy the push constants
-            pass.set_push_constants(
-                wgpu::ShaderStages::all(),
-                0,
-                &[
-                    // Time
-                    program.consts.t.to_le_bytes(),
-                    // Resolution, padded to 8 byte alignment
-                    [0, 0, 0, 0],
-                    program.consts.resolution[0].to_le_bytes(),
-                    program.consts.resolution[1].to_le_bytes(),
-                    // Aspect Ratio
-                    program.consts.aspect.to_le_bytes(),
-                    // Mouse Position, padded to 8 byte alignment
-                    [0, 0, 0, 0],
-                    program.consts.mpos[0].to_le_bytes(),
-                    program.consts.mpos[1].to_le_bytes(),
-                    // Click Position
-                    program.consts.mclick[0].to_le_bytes(),
-                    program.consts.mclick[1].to_le_bytes(),
-                ]
-                .concat(),
-            );
-            pass.draw(0..3, 0..1);
+            let size = [self.size.width, self.size.height];
+            render_passes(queue, program, size, target, encoder);
         }
     }
 
@@ -258,10 +421,11 @@ impl Renderer {
             // Display program constants.
             // Make copies since we can't borrow &self in the closure since the
             // function already mutably borrows &mut self.
-            let secs = self.start.elapsed().as_secs_f32();
+            let secs = self.clock.time();
             let size = self.size;
             let mpos = Self::transform(self.size, ui.io().mouse_pos);
             let mclick = Self::transform(self.size, self.mouse_click);
+            let status = self.status.lock().unwrap();
             ui.window("Stats")
                 .position([50.0, 50.0], imgui::Condition::FirstUseEver)
                 .size([200.0, 0.0], imgui::Condition::Always)
@@ -279,16 +443,32 @@ impl Renderer {
                         mclick[0],
                         mclick[1]
                     ));
+                    ui.separator();
+                    gui::status(ui, &status);
                 });
 
+            // Display errors from the last compile over the program
+            if let State::Failed(diagnostics) = &status.state {
+                let h = ui.io().display_size[1];
+                ui.window("Errors")
+                    .position([50.0, h - 50.0], imgui::Condition::FirstUseEver)
+                    .position_pivot([0.0, 1.0])
+                    .size([600.0, 0.0], imgui::Condition::Always)
+                    .build(|| gui::diagnostics(ui, diagnostics, status.hash.is_some()));
+            }
+            drop(status);
+
             // Display program uniforms if one is loaded
             if let Some(program) = self.program.lock().unwrap().as_mut() {
                 let uniforms = program
-                    .uniform_groups
-                    .values_mut()
-                    .flat_map(|g| g.uniforms.iter_mut());
-                for (i, (_, uniform)) in uniforms.enumerate() {
-                    ui.window(format!("Uniform: {}", &uniform.name))
+                    .groups_mut()
+                    .flat_map(|(pass, g)| g.uniforms.values_mut().map(move |u| (pass, u)));
+                for (i, (pass, uniform)) in uniforms.enumerate() {
+                    let title = match pass {
+                        Some(pass) => format!("Uniform: {} (Buffer {})", &uniform.name, pass),
+                        None => format!("Uniform: {}", &uniform.name),
+                    };
+                    ui.window(title)
                         .position(
                             [50.0 + ((i + 1) as f32 * 225.0), 50.0],
                             imgui::Condition::FirstUseEver,
@@ -297,13 +477,7 @@ impl Renderer {
                         .build(|| {
                             let n = uniform.vars.len();
                             for (j, (name, var)) in uniform.vars.iter_mut().enumerate() {
-                                match var {
-                                    Variable::Int(i) => gui::input_int(ui, name, i),
-                                    Variable::Float(f) => gui::input_float(ui, name, f),
-                                    Variable::Vec2(v) => gui::input_vec2(ui, name, v),
-                                    Variable::Vec3(v) => gui::input_vec3(ui, name, v),
-                                    Variable::Vec4(v) => gui::input_vec4(ui, name, v),
-                                }
+                                gui::input_variable(ui, name, var, &uniform.meta[j]);
 
                                 if j != n - 1 {
                                     ui.separator();
@@ -311,6 +485,192 @@ impl Renderer {
                             }
                         });
                 }
+
+                // Display textures and samplers if there are any
+                let device = &Arc::clone(&program.device);
+                let queue = &self.queue;
+                let image_paths = &mut self.image_paths;
+                let groups = program
+                    .groups_mut()
+                    .filter(|(_, g)| !g.textures.is_empty() || !g.samplers.is_empty())
+                    .collect::<Vec<_>>();
+                if !groups.is_empty() {
+                    ui.window("Textures")
+                        .position([50.0, 300.0], imgui::Condition::FirstUseEver)
+                        .size([300.0, 0.0], imgui::Condition::Always)
+                        .build(|| {
+                            for (pass, group) in groups {
+                                let mut changed = false;
+
+                                if let Some(pass) = pass {
+                                    ui.text(format!("Buffer {}", pass));
+                                    ui.separator();
+                                }
+
+                                for texture in group.textures.values_mut() {
+                                    // Buffer inputs are filled in by the renderer.
+                                    if buffer::input(&texture.name).is_some() {
+                                        continue;
+                                    }
+
+                                    let key = format!("{}/{}", pass.unwrap_or_default(), texture.name);
+                                    let path = image_paths.entry(key).or_insert_with(|| {
+                                        texture.path.as_ref().map(|p| p.display().to_string()).unwrap_or_default()
+                                    });
+
+                                    if gui::input_texture(ui, texture, path) {
+                                        match Texture::load(device, queue, &texture.name, path.as_ref()) {
+                                            Ok(t) => *texture = t,
+                                            Err(e) => log::error!("{}", e),
+                                        }
+                                        changed = true;
+                                    }
+                                    ui.separator();
+                                }
+
+                                for sampler in group.samplers.values_mut() {
+                                    if gui::input_sampler(ui, sampler) {
+                                        sampler.update(device);
+                                        changed = true;
+                                    }
+                                    ui.separator();
+                                }
+
+                                if changed {
+                                    group.bind(device);
+                                }
+                            }
+                        });
+                }
+
+                // Display export settings, exporting with the current values
+                let export = &mut self.export;
+                let size = &mut self.export_size;
+                let path = &mut self.export_path;
+                let progress = *self.export_progress.lock().unwrap();
+                let mut start = false;
+                ui.window("Export")
+                    .position([375.0, 300.0], imgui::Condition::FirstUseEver)
+                    .size([250.0, 0.0], imgui::Condition::Always)
+                    .build(|| start = gui::input_export(ui, export, size, path, progress));
+                if start {
+                    let morph = self.morph_export && self.morph.snapshots.len() > 1;
+                    self.export.morph = Some(self.morph.clone()).filter(|_| morph);
+                    self.export.timeline = Some(self.timeline.clone());
+                    self.export.modulators = Some(self.modulators.clone());
+                    export::spawn(
+                        Arc::clone(&self.device),
+                        Arc::clone(&self.queue),
+                        &self.file,
+                        export::Current::capture(program),
+                        self.export.clone(),
+                        self.export_size,
+                        Arc::clone(&self.export_progress),
+                    );
+                }
+
+                // Display the presets saved for the file
+                let presets = &self.presets;
+                let name = &mut self.preset_name;
+                let (mut load, mut save, mut delete) = (false, false, false);
+                ui.window("Presets")
+                    .position([650.0, 300.0], imgui::Condition::FirstUseEver)
+                    .size([200.0, 0.0], imgui::Condition::Always)
+                    .build(|| (load, save, delete) = gui::input_presets(ui, presets, name));
+                if load {
+                    if let Some(preset) = self.presets.get(&self.preset_name) {
+                        preset.apply(program);
+                    }
+                }
+                if save || delete {
+                    if save {
+                        self.presets.insert(&self.preset_name, Preset::capture(program));
+                    } else {
+                        self.presets.remove(&self.preset_name);
+                    }
+                    if let Err(e) = self.presets.save() {
+                        log::error!("{}", e);
+                    }
+                }
+
+                // Display snapshots to morph between
+                let morph = &mut self.morph;
+                let morph_export = &mut self.morph_export;
+                let (mut capture, mut changed) = (false, false);
+                ui.window("Morph")
+                    .position([650.0, 500.0], imgui::Condition::FirstUseEver)
+                    .size([200.0, 0.0], imgui::Condition::Always)
+                    .build(|| (capture, changed) = gui::input_morph(ui, morph, morph_export));
+                if capture {
+                    let name = format!("Snapshot {}", self.morph.snapshots.len() + 1);
+                    self.morph.snapshots.push(Snapshot::capture(&name, program));
+                }
+                if changed {
+                    self.morph.apply(program, self.morph.position);
+                }
+
+                // Display the timeline, with the variables that can be animated
+                let mut vars = vec![];
+                for (pass, group) in program.groups() {
+                    for uniform in group.uniforms.values() {
+                        for (name, var) in &uniform.vars {
+                            vars.push((preset::key(pass, &uniform.name), name.clone(), var.clone()));
+                        }
+                    }
+                }
+                let labels = vars.iter().map(|(block, name, _)| format!("{}.{}", block, name)).collect::<Vec<_>>();
+                let mut time = self.clock.time();
+                let mut playing = self.clock.is_playing();
+                let timeline = &mut self.timeline;
+                let selected = &mut self.timeline_var;
+                let (mut seek, mut key, mut save) = (false, false, false);
+                ui.window("Timeline")
+                    .position([875.0, 300.0], imgui::Condition::FirstUseEver)
+                    .size([300.0, 0.0], imgui::Condition::Always)
+                    .build(|| {
+                        (seek, key, save) =
+                            gui::input_timeline(ui, timeline, &mut time, &mut playing, &labels, selected)
+                    });
+                if playing != self.clock.is_playing() {
+                    self.clock.set_playing(playing);
+                }
+                if seek {
+                    self.clock.seek(time);
+                }
+                if key {
+                    let (block, name, value) = &vars[self.timeline_var];
+                    self.timeline.insert(block, name, value, time);
+                }
+                if save {
+                    if let Err(e) = self.timeline.save() {
+                        log::error!("{}", e);
+                    }
+                }
+
+                // Display the modulators, with the variables that can be modulated
+                let targets = modulator::targets(program);
+                let labels = targets
+                    .iter()
+                    .map(|(block, var, component)| modulator::name(block, var, *component))
+                    .collect::<Vec<_>>();
+                let modulators = &mut self.modulators;
+                let selected = &mut self.modulator_var;
+                let (mut lfo, mut expression) = (false, false);
+                ui.window("Modulators")
+                    .position([875.0, 500.0], imgui::Condition::FirstUseEver)
+                    .size([300.0, 0.0], imgui::Condition::Always)
+                    .build(|| (lfo, expression) = gui::input_modulators(ui, modulators, &labels, selected));
+                if lfo || expression {
+                    // Start out at the variable's current value
+                    let (block, var, component) = &targets[self.modulator_var];
+                    let value = modulator::value(program, &labels[self.modulator_var]).unwrap_or(0.0);
+                    let source = if lfo {
+                        Source::lfo(value as f32)
+                    } else {
+                        Source::expression(&value.to_string())
+                    };
+                    self.modulators.list.push(Modulator::new(block, var, *component, source));
+                }
             }
         }
 
@@ -335,16 +695,17 @@ impl Renderer {
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
         self.size = size;
-        Self::resize_surface(&self.device, &self.surface, size);
+        Self::resize_surface(&self.device, &self.surface, size, self.surface_usage);
     }
 
     fn resize_surface(
         device: &wgpu::Device,
         surface: &wgpu::Surface,
         size: PhysicalSize<u32>,
+        usage: wgpu::TextureUsages,
     ) {
         surface.configure(device, &wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: FORMAT,
             width: size.width,
             height: size.height,
@@ -373,3 +734,123 @@ impl Renderer {
         [x, y]
     }
 }
+
+// Create a wgpu instance and request an adapter and device supporting the
+// features needed to render programs, optionally using a software adapter.
+pub fn request_device(
+    fallback: bool,
+) -> Result<(wgpu::Instance, wgpu::Adapter, Arc<wgpu::Device>, Arc<wgpu::Queue>), String> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::PRIMARY,
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        compatible_surface: None,
+        force_fallback_adapter: fallback,
+    }))
+    .ok_or("Failed to create graphics adapter!")?;
+
+    // Fall back to passing the constants in a uniform buffer on adapters
+    // without push constants, see `Constants::SET`.
+    let push = adapter.features().contains(wgpu::Features::PUSH_CONSTANTS)
+        && adapter.limits().max_push_constant_size >= Constants::SIZE;
+    if !push {
+        log::info!("Push constants aren't supported, passing constants in a uniform buffer instead");
+    }
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("device"),
+            features: if push { wgpu::Features::PUSH_CONSTANTS } else { wgpu::Features::empty() },
+            limits: wgpu::Limits {
+                max_push_constant_size: if push { Constants::SIZE } else { 0 },
+                ..std::default::Default::default()
+            },
+        },
+        None,
+    ))
+    .map_err(|e| format!("Failed to create graphics device: {}", e))?;
+
+    Ok((instance, adapter, Arc::new(device), Arc::new(queue)))
+}
+
+// Render the buffer passes of a program followed by the program itself into
+// the target, which should be `size` pixels large.
+pub fn render_passes(
+    queue: &wgpu::Queue,
+    program: &mut Program,
+    size: [u32; 2],
+    target: &wgpu::TextureView,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    // Keep buffers the same size as the output
+    let size = [size[0].max(1), size[1].max(1)];
+    for buffer in &mut program.buffers {
+        if buffer.size() != size {
+            buffer.resize(size);
+        }
+    }
+
+    // Render each buffer pass in order, so later passes see what earlier
+    // ones rendered this frame and a pass reading itself sees last frame.
+    for i in 0..program.buffers.len() {
+        let fronts = program.buffers.iter().map(|b| b.front().clone()).collect::<Vec<_>>();
+        let buffer = &mut program.buffers[i];
+        buffer.program.consts = program.consts;
+        buffer.program.bind_buffers(&fronts);
+
+        draw(queue, &buffer.program, &buffer.back().view, encoder);
+        buffer.swap();
+    }
+
+    // Then render the main program into the target
+    let fronts = program.buffers.iter().map(|b| b.front().clone()).collect::<Vec<_>>();
+    program.bind_buffers(&fronts);
+    draw(queue, program, target, encoder);
+}
+
+// Encode a single fullscreen pass of a program into the target.
+fn draw(
+    queue: &wgpu::Queue,
+    program: &Program,
+    target: &wgpu::TextureView,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    // Update the program uniforms
+    // TODO: Don't need to do this every frame.
+    for group in program.uniform_groups.values() {
+        for uniform in group.uniforms.values() {
+            uniform.write(queue);
+        }
+    }
+    if let Some(constants) = &program.constants_buffer {
+        constants.write(queue, &program.consts);
+    }
+
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(&program.pipeline);
+
+    // Bind each uniform group
+    for (i, group) in &program.uniform_groups {
+        pass.set_bind_group(*i, &group.bind_group, &[]);
+    }
+
+    // Copy the constants, through their own uniform buffer on devices
+    // without push constants
+    match &program.constants_buffer {
+        Some(constants) => pass.set_bind_group(Constants::SET, &constants.bind_group, &[]),
+        None => pass.set_push_constants(wgpu::ShaderStages::all(), 0, &program.consts.bytes()),
+    }
+    pass.draw(0..3, 0..1);
+}